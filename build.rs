@@ -1,7 +1,15 @@
 // use std::env;
+#[cfg(feature = "libpcap")]
 use std::path::PathBuf;
 
 
+#[cfg(not(feature = "libpcap"))]
+fn main() {
+    // Pure-Rust build, nothing to link.
+}
+
+
+#[cfg(feature = "libpcap")]
 fn main() {
     // Tell cargo to look for shared libraries in the specified directory
     println!("cargo:rustc-link-search=/usr/lib");
@@ -1,7 +1,15 @@
-use libpcap_rs::{get_first_iface, get_iface_list};
+use libpcap_rs::{get_first_iface, get_iface_list, get_interfaces};
 
 
 fn main() {
     println!("{:?}", get_first_iface());
     println!("{:?}", get_iface_list());
-}
\ No newline at end of file
+
+    for iface in get_interfaces().unwrap_or_default() {
+        println!("{iface}");
+
+        if let Ok((net, mask)) = iface.lookup_net() {
+            println!("    network {net} mask {mask}");
+        }
+    }
+}
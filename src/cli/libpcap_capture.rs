@@ -1,10 +1,16 @@
 use std::path::Path;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::str::FromStr;
+use std::time::Duration;
 use std::{error::Error, thread};
 use signal_hook::{consts::SIGINT, iterator::Signals};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
 use libpcap_rs::{LibPcap, PResult, Sniff};
+use libpcap_rs::packet::{FileFormat, LibPcapPacketInfo};
+use libpcap_rs::native::{PcapngWriter, SectionHeader, InterfaceDescription};
+use libpcap_rs::stream::{OutputStream, STDIO_PATH};
+use libpcap_rs::rotate::RotatingWriter;
+use libpcap_rs::sniff::Direction;
 
 
 /// Write pcap file command
@@ -17,70 +23,202 @@ struct Cli {
     /// Capture packet count
     #[arg(short, long, default_value_t = -1)]
     count: isize,
-    /// Set promiscuous mode
-    #[arg(long, default_value_t = true)]
-    promisc: bool,
-    /// Output pcap file path
+    /// Don't put the interface into promiscuous mode, capture only the traffic of this host, like tcpdump -p
+    #[arg(short = 'p', long)]
+    no_promisc: bool,
+    /// Capture direction, like tcpdump -Q, eg: in, out or inout
+    #[arg(short = 'Q', long, value_parser = parse_direction)]
+    direction: Option<Direction>,
+    /// Output pcap file path, `-` for stdout
     #[arg(short, long)]
     output: Option<String>,
     /// Write pcap mode, eg: a or w
     #[arg(short, long)]
     mode: Option<String>,
+    /// Output file format, eg: pcap or pcapng
+    #[arg(short = 'F', long, default_value = "pcap")]
+    format: String,
+    /// Roll over to a new output file after this many millions of bytes, like tcpdump -C
+    #[arg(short = 'C', long)]
+    file_size: Option<u64>,
+    /// Roll over to a new output file every this many seconds, like tcpdump -G,
+    /// the output may hold strftime specifiers, eg: capture-%Y%m%d-%H%M%S.pcap
+    #[arg(short = 'G', long)]
+    rotate_seconds: Option<u64>,
+    /// Roll over to a new output file after this many packets
+    #[arg(long)]
+    rotate_packets: Option<u64>,
+    /// Keep only the last N rotated files, like tcpdump -W
+    #[arg(short = 'W', long)]
+    file_count: Option<usize>,
     /// BPF filter
     bpf_filter: Vec<String>,
     #[arg(short, long)]
-    /// Show capture packet
+    /// Show capture packet, on stderr when the output is stdout
     view: bool,
 }
 
 
+fn parse_direction(value: &str) -> Result<Direction, String> {
+    Direction::from_str(value).map_err(|_| format!("invalid direction: {value}"))
+}
+
+
+impl Cli {
+    fn is_rotating(&self) -> bool {
+        self.file_size.is_some() || self.rotate_seconds.is_some() || self.rotate_packets.is_some()
+    }
+}
+
+
+enum Output {
+    Pcap(LibPcap),
+    Pcapng(PcapngWriter<OutputStream>),
+    Rotating(RotatingWriter),
+}
+
+
+impl Output {
+    fn write_packet(&mut self, pkt: &LibPcapPacketInfo<'_>) -> PResult<()> {
+        match self {
+            Self::Pcap(v) => v.write_packet(pkt)?,
+            Self::Pcapng(v) => v.write_packet(0, pkt.buf, pkt.ts, pkt.len)?,
+            Self::Rotating(v) => v.write_packet(pkt)?,
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> PResult<()> {
+        match self {
+            Self::Pcap(v) => v.close(),
+            Self::Pcapng(v) => v.close(),
+            Self::Rotating(v) => v.close(),
+        }
+    }
+}
+
+
+fn rotating_writer(args: &Cli, output: &str, sniff: &Sniff) -> RotatingWriter {
+    let mut writer = RotatingWriter::new(output, sniff.linktype(), sniff.snaplen())
+        .on_close(|path| eprintln!("closed {}", path.display()));
+
+    if let Some(value) = args.file_size {
+        writer = writer.with_max_bytes(value * 1_000_000);
+    }
+
+    if let Some(value) = args.rotate_seconds {
+        writer = writer.with_interval(Duration::from_secs(value));
+    }
+
+    if let Some(value) = args.rotate_packets {
+        writer = writer.with_max_packets(value);
+    }
+
+    if let Some(value) = args.file_count {
+        writer = writer.with_max_files(value);
+    }
+
+    writer
+}
+
+
 fn sniff_pcap<'a>(args: &Cli) -> PResult<()> {
     let mut signals = Signals::new(&[SIGINT]).unwrap();
 
     let bpf_filter = args.bpf_filter.join(" ");
-    let output_pcap = if let Some(output) = &args.output {
-        let mode = if let Some(mode) = &args.mode { mode } else if Path::new(output).exists() { "a" } else { "w" };
-        Some(LibPcap::open(output, mode)?)
+
+    let mut builder = Sniff::builder(&args.iface)
+        .with_promisc(!args.no_promisc)
+        .with_filter(&bpf_filter);
+
+    if let Some(direction) = args.direction {
+        builder = builder.with_direction(direction);
+    }
+
+    let mut sniff = builder.open()?;
+
+    for warning in sniff.warnings() {
+        eprintln!("warning: {warning:?}");
+    }
+
+    let mut output_pcap = if let Some(output) = &args.output {
+        if args.is_rotating() {
+            Some(Output::Rotating(rotating_writer(args, output, &sniff)))
+        }
+        else {
+            let format = FileFormat::from_str(&args.format).unwrap_or_default();
+            // Only a pcap file is appended to by default, a pcapng one is checked when `-m a` is given
+            let mode = match &args.mode {
+                Some(mode) => mode,
+                None if format == FileFormat::Pcap && Path::new(output).exists() => "a",
+                None => "w",
+            };
+
+            match format {
+                FileFormat::Pcap => Some(Output::Pcap(LibPcap::open_with_linktype(output, mode, sniff.linktype(), sniff.snaplen())?)),
+                FileFormat::Pcapng => {
+                    let section = SectionHeader { userappl: Some("libpcap-sniff".to_string()), ..Default::default() };
+                    let mut writer = PcapngWriter::open(output, mode, section)?;
+                    let mut iface = InterfaceDescription::new(sniff.datalink(), sniff.snaplen() as u32);
+
+                    iface.name = Some(args.iface.clone());
+                    iface.filter = if bpf_filter.is_empty() { None } else { Some(bpf_filter) };
+                    writer.add_interface(iface)?;
+
+                    Some(Output::Pcapng(writer))
+                },
+            }
+        }
     }
     else {
         None
     };
-
-    let sniff = Sniff::open(&args.iface)?;
-    sniff.with_promisc(if args.promisc {1} else {0})
-         .with_filter(bpf_filter)?;
-    let sniff = Arc::new(sniff);
-    let sniff_clone = sniff.clone();
-
-    let capture_cnt = Arc::new(AtomicUsize::new(0));
-    let capture_cnt_clone = capture_cnt.clone();
+    // Ctrl-C stops the capture loop, the output is then closed and the stats printed
+    let breaker = sniff.break_handle();
 
     thread::spawn(move || {
         for _sig in signals.forever() {
-            let count = capture_cnt_clone.load(Ordering::Relaxed);
-            if let Some(stats) = sniff_clone.stats() {
-                println!("\n");
-                println!("{} packets captured", count);
-                println!("{} packets received by filter", stats.ps_recv);
-                println!("{} packets dropped by kernel", stats.ps_drop);
-                println!("{} packets dropped by iface", stats.ps_ifdrop);        
-            }
-            std::process::exit(1);
+            breaker.breakloop();
         }
     });
 
-    for pkt in sniff.capture(args.count) {
-        if args.view {
+    let breaker = sniff.break_handle();
+    let mut ret = Ok(());
+    // Shown packets would be mixed into a capture written to stdout
+    let view_stderr = args.output.as_deref() == Some(STDIO_PATH);
+
+    let count = sniff.capture_loop(args.count, |pkt| {
+        if args.view && view_stderr {
+            eprintln!("{pkt:?}");
+        }
+        else if args.view {
             println!("{pkt:?}");
         }
 
-        capture_cnt.fetch_add(1, Ordering::Relaxed);
-
-        if let Some(output_pcap) = &output_pcap {
-            output_pcap.write_timestamp(pkt.buf, pkt.timestamp);
+        if let Some(output_pcap) = &mut output_pcap {
+            if let Err(e) = output_pcap.write_packet(&pkt) {
+                ret = Err(e);
+                breaker.breakloop();
+            }
         }
+    });
+
+    if let Some(output_pcap) = &mut output_pcap {
+        output_pcap.close()?;
+    }
+
+    if let Some(stats) = sniff.stats() {
+        eprintln!("\n");
+        eprintln!("{} packets captured", count.as_ref().unwrap_or(&0));
+        eprintln!("{} packets received by filter", stats.ps_recv);
+        eprintln!("{} packets dropped by kernel", stats.ps_drop);
+        eprintln!("{} packets dropped by iface", stats.ps_ifdrop);
     }
 
+    count?;
+    ret?;
+
     Ok(())
 }
 
@@ -88,8 +226,11 @@ fn sniff_pcap<'a>(args: &Cli) -> PResult<()> {
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Cli::parse();
 
-    let ret = sniff_pcap(&args);
-    println!(">>> {ret:?}");
+    if args.is_rotating() && FileFormat::from_str(&args.format).unwrap_or_default() != FileFormat::Pcap {
+        Cli::command().error(ErrorKind::ArgumentConflict, "file rotation only supports pcap output").exit();
+    }
+
+    sniff_pcap(&args)?;
 
     Ok(())
 }
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+use std::time::Duration;
+use clap::Parser;
+use libpcap_rs::{LibPcap, LibPcapError, PResult};
+use libpcap_rs::packet::TstampPrecision;
+use libpcap_rs::edit::{Pipeline, SelectPackets, SelectTime, Truncate, TimeShift, Dedup, DedupWindow, parse_seconds};
+
+
+/// Edit pcap file command
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Input pcap file path, `-` for stdin
+    #[arg(short, long)]
+    input: String,
+    /// Output pcap file path, `-` for stdout
+    #[arg(short, long)]
+    output: String,
+    /// Keep packets N to M, 1-based and inclusive, eg: 10-20 or 10-
+    #[arg(short, long, value_parser = parse_range)]
+    range: Option<SelectPackets>,
+    /// Keep packets at or after this UNIX time, eg: 1084443427.5
+    #[arg(short = 'A', long, value_parser = parse_time)]
+    start_time: Option<Duration>,
+    /// Keep packets before this UNIX time
+    #[arg(short = 'B', long, value_parser = parse_time)]
+    end_time: Option<Duration>,
+    /// Truncate packets to this many bytes, the wire length is kept
+    #[arg(short, long)]
+    snaplen: Option<u32>,
+    /// Shift timestamps by these many seconds, eg: -3600 or 0.5
+    #[arg(short = 't', long, allow_hyphen_values = true, value_parser = TimeShift::from_str)]
+    time_shift: Option<TimeShift>,
+    /// Drop packets identical to one of the previous N packets
+    #[arg(short = 'D', long)]
+    dedup: Option<usize>,
+    /// Drop packets identical to one of the packets of the previous N seconds
+    #[arg(short = 'w', long, value_parser = parse_time)]
+    dedup_time: Option<Duration>,
+    /// Ignore MAC addresses, VLAN tags, TTL and IP checksum when looking for duplicates
+    #[arg(long)]
+    dedup_ignore_routing: bool,
+    /// Timestamp precision, eg: micro or nano
+    #[arg(long, default_value = "micro")]
+    tstamp_precision: String,
+}
+
+
+fn parse_range(value: &str) -> Result<SelectPackets, String> {
+    let invalid = |_| format!("invalid packet range: {value}");
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+    let start = if start.is_empty() { 1 } else { start.parse().map_err(invalid)? };
+    let end = if end.is_empty() { u64::MAX } else { end.parse().map_err(invalid)? };
+
+    Ok(SelectPackets(start..=end))
+}
+
+
+fn parse_time(value: &str) -> Result<Duration, String> {
+    parse_seconds(value).ok_or_else(|| format!("invalid UNIX time: {value}"))
+}
+
+
+fn edit_pcap(args: &Cli) -> PResult<()> {
+    let precision = TstampPrecision::from_str(&args.tstamp_precision).unwrap_or_default();
+    let mut input_pcap = LibPcap::open_with_tstamp_precision(&args.input, "r", precision)?;
+
+    let mut select = Pipeline::new();
+    let mut modify = Pipeline::new();
+
+    if let Some(range) = &args.range {
+        select = select.with(range.clone());
+    }
+
+    if args.start_time.is_some() || args.end_time.is_some() {
+        select = select.with(SelectTime { start: args.start_time, end: args.end_time });
+    }
+
+    if let Some(snaplen) = args.snaplen {
+        modify = modify.with(Truncate(snaplen));
+    }
+
+    if let Some(time_shift) = args.time_shift {
+        modify = modify.with(time_shift);
+    }
+
+    let dedup_window = match (args.dedup, args.dedup_time) {
+        (_, Some(v)) => Some(DedupWindow::Time(v)),
+        (Some(v), None) => Some(DedupWindow::Packets(v)),
+        _ => None,
+    };
+
+    let mut dedup = dedup_window.map(|v| {
+        Dedup::with_window(v)
+            .with_datalink(input_pcap.datalink())
+            .with_ignore_routing(args.dedup_ignore_routing)
+    });
+
+    let snaplen = args.snaplen.map(|v| (v as i32).min(input_pcap.snaplen())).unwrap_or(input_pcap.snaplen());
+    let mut output_pcap = LibPcap::open_with_header(&args.output, "w", input_pcap.linktype(), snaplen, precision)?;
+
+    let mut iter = input_pcap.read();
+    let mut count = 0;
+
+    // Duplicates are looked for on the selected packets, before they are modified
+    for pkt in select.edit(iter.by_ref()) {
+        if dedup.as_mut().map(|v| v.is_duplicate(pkt.ts, &pkt.buf)).unwrap_or_default() {
+            continue;
+        }
+
+        if let Some(pkt) = modify.apply(pkt) {
+            output_pcap.write_packet(&pkt.as_info())?;
+            count += 1;
+        }
+    }
+
+    let truncated = iter.last_error().is_some();
+
+    if let Some(e) = iter.last_error() {
+        eprintln!("{}: {e}", args.input);
+    }
+
+    output_pcap.close()?;
+
+    eprintln!("{count} packets written");
+
+    if let Some(dedup) = dedup {
+        eprintln!("{} duplicates removed", dedup.removed());
+    }
+
+    // The output only holds the packets read before the error
+    if truncated {
+        return Err(LibPcapError::ReadError { msg: "the input could not be read to the end".to_string() });
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    let args = Cli::parse();
+
+    if let Err(e) = edit_pcap(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
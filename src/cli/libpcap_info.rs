@@ -0,0 +1,77 @@
+use std::path::Path;
+use clap::Parser;
+use libpcap_rs::{LibPcapError, PResult, path::visit_dirs};
+use libpcap_rs::info::CaptureInfo;
+
+
+/// Capture file information command
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Input pcap / pcapng file or directory path, `-` for stdin, can be given several times
+    #[arg(short, long, required = true)]
+    input: Vec<String>,
+    /// Print one JSON object per line instead of text
+    #[arg(short, long)]
+    json: bool,
+}
+
+
+fn info_pcap(args: &Cli) -> PResult<()> {
+    let mut paths = vec![];
+
+    for input in &args.input {
+        let input_path = Path::new(input);
+
+        if input_path.is_dir() {
+            paths.extend(visit_dirs(input_path).unwrap_or_default().into_iter().map(|v| v.to_string_lossy().to_string()));
+        }
+        else {
+            paths.push(input.to_string());
+        }
+    }
+
+    let mut failed = 0;
+    let mut printed = 0;
+
+    // An unreadable file, e.g. a non capture file in a directory, doesn't stop the others
+    for path in &paths {
+        let info = match CaptureInfo::open(path) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                failed += 1;
+                continue;
+            },
+        };
+
+        if args.json {
+            println!("{}", info.to_json());
+        }
+        else {
+            if printed > 0 {
+                println!();
+            }
+
+            println!("{info}");
+        }
+
+        printed += 1;
+    }
+
+    if failed > 0 {
+        return Err(LibPcapError::ReadError { msg: format!("{failed} of {} files could not be read", paths.len()) });
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    let args = Cli::parse();
+
+    if let Err(e) = info_pcap(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
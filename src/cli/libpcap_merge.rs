@@ -1,5 +1,15 @@
 use std::path::Path;
-use libpcap_rs::{LibPcap, PResult, path::visit_dirs};
+use std::time::Duration;
+use std::str::FromStr;
+use libpcap_rs::{LibPcap, LibPcapError, PResult, path::visit_dirs};
+use libpcap_rs::wrapper::{LibPcapIterator, TstampPrecision};
+use libpcap_rs::packet::FileFormat;
+use libpcap_rs::native::{PcapngWriter, SectionHeader, InterfaceDescription};
+use libpcap_rs::stream::STDIO_PATH;
+use libpcap_rs::merge::{MergeIterator, MergeMode};
+use libpcap_rs::edit::{Dedup, DedupWindow, parse_seconds};
+use libpcap_rs::datalink::DataLink;
+use libpcap_rs::packet::Packet;
 use clap::Parser;
 
 
@@ -7,56 +17,196 @@ use clap::Parser;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// input pcap file path
+    /// input pcap file or directory path, `-` for stdin, gzip/zstd/xz compressed files are decompressed,
+    /// can be given several times
+    #[arg(short, long, required = true)]
+    input: Vec<String>,
     #[arg(short, long)]
-    input: String,
-    #[arg(short, long)]
-    /// Output pcap file path
+    /// Output pcap file path, `-` for stdout, compressed when it ends with .gz, .zst or .xz
     output: String,
     /// BPF filter
     bpf_filter: Vec<String>,
     /// Write pcap mode, eg: a or w
     #[arg(short, long)]
     mode: Option<String>,
+    /// Timestamp precision, eg: micro or nano
+    #[arg(long, default_value = "micro")]
+    tstamp_precision: String,
+    /// Merge input files with different link types, the output keeps the link type of the first file
+    #[arg(long)]
+    allow_mixed_linktype: bool,
+    /// Output file format, eg: pcap or pcapng.
+    /// pcapng keeps one interface per input file, so link types may differ
+    #[arg(short = 'F', long, default_value = "pcap")]
+    format: String,
+    /// Concatenate the input files instead of merging their packets in timestamp order
+    #[arg(short = 'a', long)]
+    concat: bool,
+    /// Drop packets identical to one of the previous N merged packets, e.g. seen by several capture points
+    #[arg(short = 'D', long)]
+    dedup: Option<usize>,
+    /// Drop packets identical to one of the merged packets of the previous N seconds
+    #[arg(short = 'w', long, value_parser = parse_time)]
+    dedup_time: Option<Duration>,
+    /// Ignore MAC addresses, VLAN tags, TTL and IP checksum when looking for duplicates
+    #[arg(long)]
+    dedup_ignore_routing: bool,
+}
+
+
+fn parse_time(value: &str) -> Result<Duration, String> {
+    parse_seconds(value).ok_or_else(|| format!("invalid duration: {value}"))
+}
+
+
+fn dedup(args: &Cli) -> Option<Dedup> {
+    let window = match (args.dedup, args.dedup_time) {
+        (_, Some(v)) => DedupWindow::Time(v),
+        (Some(v), None) => DedupWindow::Packets(v),
+        _ => return None,
+    };
+
+    Some(Dedup::with_window(window).with_ignore_routing(args.dedup_ignore_routing))
 }
 
 
 fn merge_pcap(args: &Cli) -> PResult<()> {
-    let mode = if let Some(mode) = &args.mode { mode } else if Path::new(&args.output).exists() { "a" } else { "w" };
-    let output_pcap = LibPcap::open(&args.output, mode)?;
-
-    let input_path = Path::new(&args.input);
-
-    if input_path.is_dir() {
-        if let Ok(paths) = visit_dirs(input_path) {
-            for path in paths {
-                let input_pcap = LibPcap::open(&path.to_string_lossy().to_string(), "r")?;
-
-                input_pcap.with_filter(&args.bpf_filter.join(" "))?;
-        
-                for pkt in input_pcap.read() {
-                    output_pcap.write_timestamp(pkt.buf, pkt.timestamp);
-                }        
-            }    
+    let format = FileFormat::from_str(&args.format).unwrap_or_default();
+    // Only a pcap file is appended to by default, a pcapng one is checked when `-m a` is given
+    let mode = match &args.mode {
+        Some(mode) => mode,
+        None if format == FileFormat::Pcap && Path::new(&args.output).exists() => "a",
+        None => "w",
+    };
+    let precision = TstampPrecision::from_str(&args.tstamp_precision).unwrap_or_default();
+
+    let mut paths = vec![];
+
+    for input in &args.input {
+        let input_path = Path::new(input);
+
+        if input_path.is_dir() {
+            paths.extend(visit_dirs(input_path).unwrap_or_default());
+        }
+        else if input_path.is_file() || input == STDIO_PATH {
+            paths.push(input_path.to_path_buf());
+        }
+    }
+
+    let bpf_filter = args.bpf_filter.join(" ");
+    let mut inputs = vec![];
+
+    for path in paths {
+        let path = path.to_string_lossy().to_string();
+        let input_pcap = LibPcap::open_with_tstamp_precision(&path, "r", precision)?;
+
+        input_pcap.with_filter(&bpf_filter)?;
+        inputs.push((path, input_pcap));
+    }
+
+    let paths: Vec<String> = inputs.iter().map(|(path, _)| path.clone()).collect();
+    let merge_mode = if args.concat { MergeMode::Concat } else { MergeMode::Time };
+    // Packets are compared with the link type of their own input, they may differ
+    let datalinks: Vec<DataLink> = inputs.iter().map(|(_, v)| v.datalink()).collect();
+    let mut dedup = dedup(args);
+    let mut is_duplicate = |i: usize, pkt: &Packet| {
+        dedup.as_mut().map(|v| v.is_duplicate_on(datalinks[i], pkt.ts, &pkt.buf)).unwrap_or_default()
+    };
+
+    if format == FileFormat::Pcapng {
+        let section = SectionHeader { userappl: Some("libpcap-merge".to_string()), ..Default::default() };
+        let mut output_pcap = PcapngWriter::open(&args.output, mode, section)?;
+        let mut interface_ids = vec![];
+
+        // One interface per input file
+        for (path, input_pcap) in &inputs {
+            let mut iface = InterfaceDescription::new(input_pcap.datalink(), input_pcap.snaplen() as u32)
+                .with_tstamp_precision(precision);
+
+            iface.name = Some(path.to_string());
+            iface.filter = if bpf_filter.is_empty() { None } else { Some(bpf_filter.clone()) };
+
+            interface_ids.push(output_pcap.add_interface(iface)?);
+        }
+
+        let mut packets = MergeIterator::new(inputs.iter_mut().map(|(_, v)| v.read()), merge_mode);
+
+        for (i, pkt) in packets.by_ref() {
+            if !is_duplicate(i, &pkt) {
+                output_pcap.write_packet(interface_ids[i], &pkt.buf, pkt.ts, pkt.len)?;
+            }
+        }
+
+        output_pcap.close()?;
+        print_removed(&dedup);
+
+        return check_inputs(&paths, packets.into_inner());
+    }
+
+    let Some((_, first)) = inputs.first() else {
+        return Ok(());
+    };
+
+    for (path, input_pcap) in &inputs {
+        if input_pcap.linktype() != first.linktype() && !args.allow_mixed_linktype {
+            return Err(LibPcapError::LinkTypeMismatch {
+                path: path.to_string(),
+                expected: first.linktype(),
+                found: input_pcap.linktype(),
+            });
+        }
+    }
+
+    let mut output_pcap = LibPcap::open_like(&args.output, mode, first)?;
+
+    let mut packets = MergeIterator::new(inputs.iter_mut().map(|(_, v)| v.read()), merge_mode);
+
+    for (i, pkt) in packets.by_ref() {
+        if !is_duplicate(i, &pkt) {
+            output_pcap.write_packet(&pkt.as_info())?;
         }
     }
-    else if input_path.is_file() {
-        let input_pcap = LibPcap::open(&args.input, "r")?;
 
-        input_pcap.with_filter(&args.bpf_filter.join(" "))?;
+    output_pcap.close()?;
+    print_removed(&dedup);
+
+    check_inputs(&paths, packets.into_inner())
+}
+
+
+/// Report the inputs whose reading stopped on an error, their remaining packets are missing from the output
+fn check_inputs(paths: &[String], inputs: Vec<LibPcapIterator<'_>>) -> PResult<()> {
+    let mut count = 0;
 
-        for pkt in input_pcap.read() {
-            output_pcap.write_timestamp(pkt.buf, pkt.timestamp);
+    for (path, input) in paths.iter().zip(&inputs) {
+        if let Some(e) = input.last_error() {
+            eprintln!("{path}: {e}");
+            count += 1;
         }
     }
 
+    if count > 0 {
+        return Err(LibPcapError::ReadError {
+            msg: format!("{count} of {} inputs could not be read to the end", inputs.len()),
+        });
+    }
+
     Ok(())
 }
 
 
+fn print_removed(dedup: &Option<Dedup>) {
+    if let Some(dedup) = dedup {
+        eprintln!("{} duplicates removed", dedup.removed());
+    }
+}
+
+
 fn main() {
     let args = Cli::parse();
 
-    let ret = merge_pcap(&args);
-    println!("{ret:?}");
+    if let Err(e) = merge_pcap(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 }
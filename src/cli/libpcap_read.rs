@@ -6,7 +6,7 @@ use libpcap_rs::{LibPcap, PResult, Layer};
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Input pcap file path
+    /// Input pcap file path, `-` for stdin
     #[arg(short, long)]
     input: String,
     #[arg(long)]
@@ -15,9 +15,11 @@ struct Cli {
 
 
 fn read_pcap(args: &Cli) -> PResult<()> {
-    let f = LibPcap::open(&args.input, "r")?;
+    let mut f = LibPcap::open(&args.input, "r")?;
 
-    for pkt in f.read() {
+    let mut iter = f.read();
+
+    for pkt in iter.by_ref() {
         if let Ok((_, layer)) = jppe::decode_borrow::<Layer<'_>>(&pkt.buf) {
             let smac = layer.layer12.smac.to_string();
             let dmac = layer.layer12.dmac.to_string();
@@ -61,6 +63,10 @@ fn read_pcap(args: &Cli) -> PResult<()> {
         }
     }        
 
+    if let Some(e) = iter.last_error() {
+        eprintln!("{e}");
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,101 @@
+use std::str::FromStr;
+use std::time::Duration;
+use clap::Parser;
+use libpcap_rs::{LibPcap, LibPcapError, PResult};
+use libpcap_rs::packet::TstampPrecision;
+use libpcap_rs::split::{SplitBy, Splitter, DEFAULT_MAX_OPEN};
+
+
+/// Split pcap file command
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Input pcap file path, `-` for stdin
+    #[arg(short, long)]
+    input: String,
+    /// Output path template, `{index}` is the packet count or time window number, `{key}` the flow, host or vlan,
+    /// strftime specifiers are replaced with the first packet time, eg: split-{key}.pcap
+    #[arg(short, long)]
+    output: String,
+    /// Split every N packets
+    #[arg(short, long)]
+    count: Option<u64>,
+    /// Split every N seconds
+    #[arg(short = 'G', long)]
+    seconds: Option<u64>,
+    /// Split by key, eg: flow, host or vlan
+    #[arg(short, long)]
+    by: Option<String>,
+    /// Maximum number of simultaneously open output files, 64 by default
+    #[arg(long)]
+    max_open: Option<usize>,
+    /// Timestamp precision, eg: micro or nano
+    #[arg(long, default_value = "micro")]
+    tstamp_precision: String,
+}
+
+
+fn split_by(args: &Cli) -> Option<SplitBy> {
+    if let Some(count) = args.count {
+        return Some(SplitBy::Packets(count));
+    }
+
+    if let Some(seconds) = args.seconds {
+        return Some(SplitBy::Interval(Duration::from_secs(seconds)));
+    }
+
+    match args.by.as_deref()? {
+        "flow" => Some(SplitBy::Flow),
+        "host" => Some(SplitBy::Host),
+        "vlan" => Some(SplitBy::Vlan),
+        _ => None,
+    }
+}
+
+
+fn split_pcap(args: &Cli, by: SplitBy) -> PResult<()> {
+    let precision = TstampPrecision::from_str(&args.tstamp_precision).unwrap_or_default();
+    let mut input_pcap = LibPcap::open_with_tstamp_precision(&args.input, "r", precision)?;
+
+    let mut splitter = Splitter::new(by, &args.output, input_pcap.linktype(), input_pcap.snaplen())
+        .with_tstamp_precision(precision)
+        .with_max_open(args.max_open.unwrap_or(DEFAULT_MAX_OPEN));
+
+    let mut iter = input_pcap.read();
+
+    while let Some(pkt) = iter.next_packet() {
+        splitter.write_packet(&pkt)?;
+    }
+
+    let truncated = iter.last_error().is_some();
+
+    if let Some(e) = iter.last_error() {
+        eprintln!("{}: {e}", args.input);
+    }
+
+    splitter.close()?;
+
+    eprintln!("{} files written", splitter.files().len());
+
+    // The packets read before the error are split, the input still wasn't
+    if truncated {
+        return Err(LibPcapError::ReadError { msg: "the input could not be read to the end".to_string() });
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    let args = Cli::parse();
+
+    let Some(by) = split_by(&args) else {
+        eprintln!("one of --count, --seconds or --by flow|host|vlan is required.");
+        std::process::exit(1);
+    };
+
+    if let Err(e) = split_pcap(&args, by) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+use clap::Parser;
+use libpcap_rs::{LibPcap, LibPcapError, PResult};
+use libpcap_rs::stats::CaptureStats;
+
+
+/// Capture statistics command
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Input pcap file path, `-` for stdin
+    #[arg(short, long)]
+    input: String,
+    /// Print the protocol hierarchy
+    #[arg(short, long)]
+    phs: bool,
+    /// Print the conversations of a layer, eg: eth, ip, tcp or udp, can be given several times
+    #[arg(short, long)]
+    conv: Vec<String>,
+}
+
+
+fn stats_pcap(args: &Cli) -> PResult<()> {
+    let mut input_pcap = LibPcap::open(&args.input, "r")?;
+
+    let mut stats = CaptureStats::new(input_pcap.datalink());
+    let mut iter = input_pcap.read();
+
+    while let Some(pkt) = iter.next_packet() {
+        stats.add_packet(&pkt);
+    }
+
+    let truncated = iter.last_error().is_some();
+
+    if let Some(e) = iter.last_error() {
+        eprintln!("{}: {e}", args.input);
+    }
+
+    // Everything by default
+    let conv = if args.phs || !args.conv.is_empty() {
+        args.conv.clone()
+    }
+    else {
+        ["eth", "ip", "tcp", "udp"].iter().map(|v| v.to_string()).collect()
+    };
+
+    let mut sections = vec![];
+
+    if args.phs || args.conv.is_empty() {
+        sections.push(format!("Protocol Hierarchy Statistics\n{}", stats.hierarchy.to_string().trim_end()));
+    }
+
+    for layer in conv {
+        match layer.as_str() {
+            "eth" => sections.push(format!("Ethernet Conversations\n{}", stats.eth)),
+            "ip" => sections.push(format!("IP Conversations\n{}", stats.ip)),
+            "tcp" => sections.push(format!("TCP Conversations\n{}", stats.tcp)),
+            "udp" => sections.push(format!("UDP Conversations\n{}", stats.udp)),
+            _ => eprintln!("unknown conversation layer: {layer}, eg: eth, ip, tcp or udp"),
+        }
+    }
+
+    println!("{}", sections.join("\n\n"));
+
+    // The statistics only cover the packets read before the error
+    if truncated {
+        return Err(LibPcapError::ReadError { msg: "the input could not be read to the end".to_string() });
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    let args = Cli::parse();
+
+    if let Err(e) = stats_pcap(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
@@ -7,7 +7,7 @@ use libpcap_rs::{LibPcap, PResult, ChecksumLayer};
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Output pcap file path
+    /// Output pcap file path, `-` for stdout
     #[arg(short, long)]
     output: String,
     /// Byte stream
@@ -30,7 +30,7 @@ struct Cli {
 
 fn write_pcap(args: &Cli) -> PResult<()> {
     let mode = if let Some(mode) = &args.mode { mode } else if Path::new(&args.output).exists() { "a" } else { "w" };
-    let output_pcap = LibPcap::open(&args.output, mode)?;
+    let mut output_pcap = LibPcap::open(&args.output, mode)?;
     
     if let Ok(mut payload) = hex::decode(&args.payload) {
         if args.checksum {
@@ -62,14 +62,16 @@ fn write_pcap(args: &Cli) -> PResult<()> {
         }
 
         if let Some(timestamp) = args.timestamp {
-            output_pcap.write_timestamp(&payload, timestamp as i64);
+            output_pcap.write_timestamp(&payload, timestamp as i64)?;
         }
         else {
-            output_pcap.write(&payload);
+            output_pcap.write(&payload)?;
         }
+
+        output_pcap.close()?;
     }
     else {
-        println!("payload format error.");
+        eprintln!("payload format error.");
     }
 
     Ok(())
@@ -80,5 +82,5 @@ fn main() {
     let args = Cli::parse();
 
     let ret = write_pcap(&args);
-    println!("{ret:?}");
+    eprintln!("{ret:?}");
 }
\ No newline at end of file
@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{BufRead, Read, Write};
+use std::path::Path;
+use jkcenum::JkcEnum;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::native::pcap::read_full;
+
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+const XZ_MAGIC: &[u8] = b"\xfd\x37\x7a\x58\x5a\x00";
+
+
+/// Compression codec of a capture file
+#[derive(Debug, Default, Clone, Copy, JkcEnum, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    #[jenum(rename="none")]
+    None,
+    #[jenum(rename="gzip")]
+    Gzip,
+    #[jenum(rename="zstd")]
+    Zstd,
+    #[jenum(rename="xz")]
+    Xz,
+}
+
+
+impl Compression {
+    /// Detect the codec from the first bytes of a stream
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use libpcap_rs::compress::Compression;
+    ///
+    /// assert_eq!(Compression::detect(b"\x1f\x8b\x08\x00"), Compression::Gzip);
+    /// assert_eq!(Compression::detect(b"\xd4\xc3\xb2\xa1"), Compression::None);
+    /// ```
+    ///
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        }
+        else if magic.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        }
+        else if magic.starts_with(XZ_MAGIC) {
+            Self::Xz
+        }
+        else {
+            Self::None
+        }
+    }
+
+    /// Codec implied by the file extension, `.gz`, `.zst` or `.xz`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|v| v.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("zst") => Self::Zstd,
+            Some("xz") => Self::Xz,
+            _ => Self::None,
+        }
+    }
+
+    /// Detect the codec of an existing file, `None` if it can't be read
+    pub fn detect_file<P: AsRef<Path>>(path: P) -> Self {
+        let mut magic = [0; XZ_MAGIC.len()];
+
+        match File::open(path).and_then(|mut v| read_full(&mut v, &mut magic)) {
+            Ok(n) => Self::detect(&magic[..n]),
+            Err(_) => Self::None,
+        }
+    }
+
+    /// Codec to write `path` with, the one of the existing data when appending, else from the extension
+    pub fn for_output<P: AsRef<Path>>(path: P, append: bool) -> Self {
+        let path = path.as_ref();
+
+        if append && path.metadata().map(|v| v.len() > 0).unwrap_or_default() {
+            Self::detect_file(path)
+        }
+        else {
+            Self::from_path(path)
+        }
+    }
+
+    /// File extension of the codec, without the dot
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gz"),
+            Self::Zstd => Some("zst"),
+            Self::Xz => Some("xz"),
+        }
+    }
+
+    /// Whether the codec was compiled in, see the `gzip`, `zstd` and `xz` features
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Self::None => true,
+            Self::Gzip => cfg!(feature = "gzip"),
+            Self::Zstd => cfg!(feature = "zstd"),
+            Self::Xz => cfg!(feature = "xz"),
+        }
+    }
+
+    fn unsupported(&self) -> LibPcapError {
+        LibPcapError::UnsupportedCompression { name: self.to_string() }
+    }
+}
+
+
+/// Reader decompressing a stream whose codec is detected from its magic bytes
+pub enum CompressReader<R: BufRead> {
+    Plain(R),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::bufread::MultiGzDecoder<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, R>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::bufread::XzDecoder<R>),
+}
+
+
+impl<R: BufRead> CompressReader<R> {
+    /// Peek at the stream and wrap it in the matching decoder
+    pub fn new(mut reader: R) -> PResult<Self> {
+        let compression = Compression::detect(reader.fill_buf()?);
+
+        Self::with_compression(reader, compression)
+    }
+
+    /// Wrap the stream in the decoder of a known codec
+    pub fn with_compression(reader: R, compression: Compression) -> PResult<Self> {
+        match compression {
+            Compression::None => Ok(Self::Plain(reader)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Self::Gzip(flate2::bufread::MultiGzDecoder::new(reader))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Self::Zstd(zstd::stream::read::Decoder::with_buffer(reader)?)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Self::Xz(xz2::bufread::XzDecoder::new_multi_decoder(reader))),
+            #[allow(unreachable_patterns)]
+            _ => Err(compression.unsupported()),
+        }
+    }
+
+    pub fn compression(&self) -> Compression {
+        match self {
+            Self::Plain(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Self::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => Compression::Zstd,
+            #[cfg(feature = "xz")]
+            Self::Xz(_) => Compression::Xz,
+        }
+    }
+}
+
+
+impl<R: BufRead> Read for CompressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(v) => v.read(buf),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(v) => v.read(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(v) => v.read(buf),
+            #[cfg(feature = "xz")]
+            Self::Xz(v) => v.read(buf),
+        }
+    }
+}
+
+
+/// Writer compressing its output, the stream is finished when dropped
+pub enum CompressWriter<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::write::XzEncoder<W>),
+}
+
+
+impl<W: Write> CompressWriter<W> {
+    pub fn new(writer: W, compression: Compression) -> PResult<Self> {
+        match compression {
+            Compression::None => Ok(Self::Plain(writer)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Self::Gzip(flate2::write::GzEncoder::new(writer, flate2::Compression::default()))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Self::Zstd(zstd::stream::write::Encoder::new(writer, 0)?)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Self::Xz(xz2::write::XzEncoder::new(writer, 6))),
+            #[allow(unreachable_patterns)]
+            _ => Err(compression.unsupported()),
+        }
+    }
+
+    pub fn compression(&self) -> Compression {
+        match self {
+            Self::Plain(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Self::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => Compression::Zstd,
+            #[cfg(feature = "xz")]
+            Self::Xz(_) => Compression::Xz,
+        }
+    }
+
+    /// Write the trailer of the compressed stream, nothing can be written afterwards
+    pub fn try_finish(&mut self) -> PResult<()> {
+        match self {
+            Self::Plain(v) => v.flush()?,
+            #[cfg(feature = "gzip")]
+            Self::Gzip(v) => { v.try_finish()?; v.get_mut().flush()? },
+            #[cfg(feature = "zstd")]
+            Self::Zstd(v) => { v.do_finish()?; v.get_mut().flush()? },
+            #[cfg(feature = "xz")]
+            Self::Xz(v) => { v.try_finish()?; v.get_mut().flush()? },
+        }
+
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        match self {
+            Self::Plain(v) => v,
+            #[cfg(feature = "gzip")]
+            Self::Gzip(v) => v.get_ref(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(v) => v.get_ref(),
+            #[cfg(feature = "xz")]
+            Self::Xz(v) => v.get_ref(),
+        }
+    }
+}
+
+
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(v) => v.write(buf),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(v) => v.write(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(v) => v.write(buf),
+            #[cfg(feature = "xz")]
+            Self::Xz(v) => v.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(v) => v.flush(),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(v) => v.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(v) => v.flush(),
+            #[cfg(feature = "xz")]
+            Self::Xz(v) => v.flush(),
+        }
+    }
+}
+
+
+impl<W: Write> Drop for CompressWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.try_finish();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let input = b"\xd4\xc3\xb2\xa1\x02\x00\x04\x00 some pcap bytes";
+
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd, Compression::Xz] {
+            if !compression.is_supported() {
+                assert!(CompressWriter::new(Vec::new(), compression).is_err());
+                continue;
+            }
+
+            let mut writer = CompressWriter::new(Vec::new(), compression).unwrap();
+            writer.write_all(input).unwrap();
+            writer.try_finish().unwrap();
+
+            let buf = writer.get_ref().clone();
+            assert_eq!(Compression::detect(&buf), compression);
+
+            let mut reader = CompressReader::new(&buf[..]).unwrap();
+            let mut output = vec![];
+            reader.read_to_end(&mut output).unwrap();
+            assert_eq!(output, input);
+        }
+    }
+}
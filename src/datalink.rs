@@ -0,0 +1,225 @@
+use std::str::FromStr;
+#[cfg(feature = "libpcap")]
+use std::ffi::{CString, CStr};
+use crate::errors::LibPcapError;
+#[cfg(feature = "libpcap")]
+use crate::make_cstr;
+#[cfg(feature = "libpcap")]
+use crate::libpcap::{pcap_datalink_val_to_name, pcap_datalink_val_to_description, pcap_datalink_name_to_val};
+
+
+/// Link type used when none is given, `DLT_EN10MB`
+pub const DEFAULT_LINKTYPE: i32 = 1;
+/// Snapshot length used when none is given
+pub const DEFAULT_SNAPLEN: i32 = 65535;
+
+
+/// Data link type, `DLT_*`
+/// 
+/// # Example:
+/// 
+/// ```rust
+/// use libpcap_rs::DataLink;
+/// 
+/// assert_eq!(DataLink::from(1), DataLink::En10mb);
+/// assert_eq!(DataLink::En10mb.name().as_deref(), Some("EN10MB"));
+/// assert_eq!("RAW".parse::<DataLink>().ok(), Some(DataLink::Raw));
+/// assert_eq!(i32::from(DataLink::Other(147)), 147);
+/// ```
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataLink {
+    /// BSD loopback encapsulation
+    Null,
+    /// Ethernet
+    En10mb,
+    /// IEEE 802.5 Token Ring
+    Ieee802,
+    /// PPP
+    Ppp,
+    /// Raw IP
+    Raw,
+    /// PPP in HDLC-like framing
+    PppSerial,
+    /// PPPoE
+    PppEther,
+    /// IEEE 802.11 wireless LAN
+    Ieee80211,
+    /// OpenBSD loopback encapsulation
+    Loop,
+    /// Linux cooked capture
+    LinuxSll,
+    /// Radiotap header + IEEE 802.11
+    Ieee80211Radio,
+    /// Raw IPv4
+    Ipv4,
+    /// Raw IPv6
+    Ipv6,
+    /// Linux cooked capture v2
+    LinuxSll2,
+    /// Any other `DLT_*` value
+    Other(i32),
+}
+
+
+impl DataLink {
+    /// `DLT_*` value
+    pub fn value(&self) -> i32 {
+        match self {
+            Self::Null => 0,
+            Self::En10mb => 1,
+            Self::Ieee802 => 6,
+            Self::Ppp => 9,
+            Self::Raw => 12,
+            Self::PppSerial => 50,
+            Self::PppEther => 51,
+            Self::Ieee80211 => 105,
+            Self::Loop => 108,
+            Self::LinuxSll => 113,
+            Self::Ieee80211Radio => 127,
+            Self::Ipv4 => 228,
+            Self::Ipv6 => 229,
+            Self::LinuxSll2 => 276,
+            Self::Other(v) => *v,
+        }
+    }
+
+    /// Name of the link type, eg: `EN10MB`
+    #[cfg(feature = "libpcap")]
+    pub fn name(&self) -> Option<String> {
+        let value = unsafe { pcap_datalink_val_to_name(self.value()) };
+
+        if value.is_null() { None } else { Some(make_cstr!(value)) }
+    }
+
+    /// Name of the link type, eg: `EN10MB`
+    #[cfg(not(feature = "libpcap"))]
+    pub fn name(&self) -> Option<String> {
+        DATALINKS.iter().find(|v| v.0 == self.value()).map(|v| v.1.to_string())
+    }
+
+    /// Short description of the link type, eg: `Ethernet`
+    #[cfg(feature = "libpcap")]
+    pub fn description(&self) -> Option<String> {
+        let value = unsafe { pcap_datalink_val_to_description(self.value()) };
+
+        if value.is_null() { None } else { Some(make_cstr!(value)) }
+    }
+
+    /// Short description of the link type, eg: `Ethernet`
+    #[cfg(not(feature = "libpcap"))]
+    pub fn description(&self) -> Option<String> {
+        DATALINKS.iter().find(|v| v.0 == self.value()).map(|v| v.2.to_string())
+    }
+
+    /// Convert a `LINKTYPE_*` value stored in a capture file to the `DLT_*` value
+    pub fn from_linktype(value: u32) -> Self {
+        Self::from(match value {
+            101 => 12,
+            102 => 15,
+            103 => 16,
+            106 => 19,
+            v => v as i32,
+        })
+    }
+
+    /// `LINKTYPE_*` value stored in a capture file
+    pub fn linktype(&self) -> u32 {
+        match self.value() {
+            12 => 101,
+            15 => 102,
+            16 => 103,
+            19 => 106,
+            v => v as u32,
+        }
+    }
+}
+
+
+impl From<i32> for DataLink {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::Null,
+            1 => Self::En10mb,
+            6 => Self::Ieee802,
+            9 => Self::Ppp,
+            12 => Self::Raw,
+            50 => Self::PppSerial,
+            51 => Self::PppEther,
+            105 => Self::Ieee80211,
+            108 => Self::Loop,
+            113 => Self::LinuxSll,
+            127 => Self::Ieee80211Radio,
+            228 => Self::Ipv4,
+            229 => Self::Ipv6,
+            276 => Self::LinuxSll2,
+            v => Self::Other(v),
+        }
+    }
+}
+
+
+impl From<DataLink> for i32 {
+    fn from(value: DataLink) -> Self {
+        value.value()
+    }
+}
+
+
+impl FromStr for DataLink {
+    type Err = LibPcapError;
+
+    /// Parse a link type name, with or without the `DLT_` prefix
+    #[cfg(feature = "libpcap")]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = CString::new(s.trim_start_matches("DLT_")).unwrap_or_default();
+        let value = unsafe { pcap_datalink_name_to_val(name.as_ptr()) };
+
+        if value < 0 {
+            return Err(LibPcapError::InvalidDataLink { name: s.to_string() });
+        }
+
+        Ok(Self::from(value))
+    }
+
+    /// Parse a link type name, with or without the `DLT_` prefix
+    #[cfg(not(feature = "libpcap"))]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim_start_matches("DLT_");
+
+        match DATALINKS.iter().find(|v| v.1.eq_ignore_ascii_case(name)) {
+            Some(v) => Ok(Self::from(v.0)),
+            None => Err(LibPcapError::InvalidDataLink { name: s.to_string() }),
+        }
+    }
+}
+
+
+impl std::fmt::Display for DataLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{}", self.value()),
+        }
+    }
+}
+
+
+/// Names and descriptions used when libpcap isn't linked
+#[cfg(not(feature = "libpcap"))]
+const DATALINKS: &[(i32, &str, &str)] = &[
+    (0, "NULL", "BSD loopback"),
+    (1, "EN10MB", "Ethernet"),
+    (6, "IEEE802", "Token ring"),
+    (9, "PPP", "PPP"),
+    (12, "RAW", "Raw IP"),
+    (50, "PPP_SERIAL", "PPP over serial"),
+    (51, "PPP_ETHER", "PPPoE"),
+    (105, "IEEE802_11", "802.11"),
+    (108, "LOOP", "OpenBSD loopback"),
+    (113, "LINUX_SLL", "Linux cooked v1"),
+    (127, "IEEE802_11_RADIO", "802.11 plus radiotap header"),
+    (228, "IPV4", "Raw IPv4"),
+    (229, "IPV6", "Raw IPv6"),
+    (276, "LINUX_SLL2", "Linux cooked v2"),
+];
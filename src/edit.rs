@@ -0,0 +1,502 @@
+use std::collections::hash_map::DefaultHasher;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::errors::LibPcapError;
+use crate::packet::Packet;
+use crate::datalink::DataLink;
+use crate::net::vlan::{untagged, ETHERNET_HEADER_LEN};
+
+
+/// A step of an edit pipeline, changes a packet or drops it
+pub trait Transform {
+    /// Change `pkt` or drop it by returning `None`
+    ///
+    /// # Args:
+    ///
+    /// - `number`: 1-based position of the packet in the input, as shown by Wireshark
+    /// - `pkt`: packet left by the previous steps
+    ///
+    fn apply(&mut self, number: u64, pkt: Packet) -> Option<Packet>;
+}
+
+
+impl<F: FnMut(u64, Packet) -> Option<Packet>> Transform for F {
+    fn apply(&mut self, number: u64, pkt: Packet) -> Option<Packet> {
+        self(number, pkt)
+    }
+}
+
+
+/// Keep the packets whose number is in a range, like `editcap -r`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectPackets(pub RangeInclusive<u64>);
+
+
+impl Transform for SelectPackets {
+    fn apply(&mut self, number: u64, pkt: Packet) -> Option<Packet> {
+        if self.0.contains(&number) { Some(pkt) } else { None }
+    }
+}
+
+
+/// Keep the packets with `start <= ts < end`, like `editcap -A / -B`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectTime {
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+}
+
+
+impl Transform for SelectTime {
+    fn apply(&mut self, _number: u64, pkt: Packet) -> Option<Packet> {
+        let after_start = self.start.map(|v| pkt.ts >= v).unwrap_or(true);
+        let before_end = self.end.map(|v| pkt.ts < v).unwrap_or(true);
+
+        if after_start && before_end { Some(pkt) } else { None }
+    }
+}
+
+
+/// Cut packets to a snapshot length, like `editcap -s`, the wire length is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncate(pub u32);
+
+
+impl Transform for Truncate {
+    fn apply(&mut self, _number: u64, mut pkt: Packet) -> Option<Packet> {
+        if pkt.buf.len() > self.0 as usize {
+            pkt.len = pkt.len.max(pkt.caplen);
+            pkt.buf.truncate(self.0 as usize);
+            pkt.caplen = self.0;
+        }
+
+        Some(pkt)
+    }
+}
+
+
+/// Move timestamps forward or backward, like `editcap -t`
+///
+/// # Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use libpcap_rs::edit::TimeShift;
+///
+/// assert_eq!("-1.5".parse::<TimeShift>().unwrap(), TimeShift::Backward(Duration::from_millis(1500)));
+/// assert_eq!("3600".parse::<TimeShift>().unwrap(), TimeShift::Forward(Duration::from_secs(3600)));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeShift {
+    Forward(Duration),
+    Backward(Duration),
+}
+
+
+impl Transform for TimeShift {
+    fn apply(&mut self, _number: u64, mut pkt: Packet) -> Option<Packet> {
+        pkt.ts = match *self {
+            Self::Forward(v) => pkt.ts + v,
+            Self::Backward(v) => pkt.ts.saturating_sub(v),
+        };
+        pkt.timestamp = pkt.ts.as_secs() as i64;
+
+        Some(pkt)
+    }
+}
+
+
+impl FromStr for TimeShift {
+    type Err = LibPcapError;
+
+    /// Parse signed seconds with an optional fraction, e.g. `-0.25`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || LibPcapError::InvalidTime { value: s.to_string() };
+
+        match s.strip_prefix('-') {
+            Some(v) => Ok(Self::Backward(parse_seconds(v).ok_or_else(invalid)?)),
+            None => Ok(Self::Forward(parse_seconds(s.strip_prefix('+').unwrap_or(s)).ok_or_else(invalid)?)),
+        }
+    }
+}
+
+
+/// Parse seconds with an optional fraction, e.g. a UNIX time `1084443427.311224`, without float rounding
+pub fn parse_seconds(value: &str) -> Option<Duration> {
+    let (secs, frac) = value.split_once('.').unwrap_or((value, ""));
+
+    if secs.is_empty() && frac.is_empty() || frac.len() > 9 || !frac.bytes().all(|v| v.is_ascii_digit()) {
+        return None;
+    }
+
+    let secs = if secs.is_empty() { 0 } else { secs.parse::<u64>().ok()? };
+    let nanos = if frac.is_empty() { 0 } else { format!("{frac:0<9}").parse::<u32>().ok()? };
+
+    Some(Duration::new(secs, nanos))
+}
+
+
+/// Window in which a packet is compared with the previous ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupWindow {
+    /// The last N packets, like `editcap -D`
+    Packets(usize),
+    /// The packets of the last duration, like `editcap -w`
+    Time(Duration),
+}
+
+
+/// Drop packets identical to a previous one within a window, e.g. duplicates from SPAN ports
+///
+/// Packets are compared by hash. Fields changed when a packet is routed, the MAC addresses,
+/// VLAN tags, IP TTL / hop limit and IPv4 checksum, can be ignored with `with_ignore_routing`.
+///
+/// # Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use libpcap_rs::LibPcap;
+/// use libpcap_rs::edit::{Dedup, DedupWindow};
+///
+/// let mut input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+/// let mut packets = Dedup::with_window(DedupWindow::Time(Duration::from_millis(10)))
+///     .with_ignore_routing(true)
+///     .dedup(input.read());
+///
+/// let count = packets.by_ref().count();
+///
+/// println!("{count} packets, {} duplicates removed", packets.removed());
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dedup {
+    window: DedupWindow,
+    datalink: DataLink,
+    ignore_routing: bool,
+    /// `(timestamp, hash)` of the packets in the window
+    recent: VecDeque<(Duration, u64)>,
+    seen: HashMap<u64, usize>,
+    removed: u64,
+}
+
+
+impl Dedup {
+    /// Compare packets with the previous `window` packets
+    pub fn new(window: usize) -> Self {
+        Self::with_window(DedupWindow::Packets(window))
+    }
+
+    pub fn with_window(window: DedupWindow) -> Self {
+        Self {
+            window,
+            datalink: DataLink::En10mb,
+            ignore_routing: false,
+            recent: VecDeque::new(),
+            seen: HashMap::new(),
+            removed: 0,
+        }
+    }
+
+    /// Link type of the packets, Ethernet by default, to find the fields ignored by `with_ignore_routing`
+    pub fn with_datalink(mut self, datalink: DataLink) -> Self {
+        self.datalink = datalink;
+        self
+    }
+
+    /// Ignore MAC addresses, VLAN tags, IP TTL / hop limit and IPv4 checksum
+    pub fn with_ignore_routing(mut self, value: bool) -> Self {
+        self.ignore_routing = value;
+        self
+    }
+
+    /// Number of duplicates dropped so far
+    pub fn removed(&self) -> u64 {
+        self.removed
+    }
+
+    /// Whether a packet duplicates one in the window, it is added to the window otherwise
+    pub fn is_duplicate(&mut self, ts: Duration, buf: &[u8]) -> bool {
+        self.is_duplicate_on(self.datalink, ts, buf)
+    }
+
+    /// Like `is_duplicate` for a packet of its own link type, e.g. merged from captures of different link types
+    pub fn is_duplicate_on(&mut self, datalink: DataLink, ts: Duration, buf: &[u8]) -> bool {
+        let digest = self.digest(datalink, buf);
+
+        match self.window {
+            DedupWindow::Packets(n) => {
+                while self.recent.len() > n {
+                    self.pop_front();
+                }
+            },
+            DedupWindow::Time(window) => {
+                while self.recent.front().map(|(v, _)| ts.saturating_sub(*v) > window).unwrap_or_default() {
+                    self.pop_front();
+                }
+            },
+        }
+
+        if self.seen.contains_key(&digest) {
+            self.removed += 1;
+            return true;
+        }
+
+        if self.window != DedupWindow::Packets(0) {
+            self.recent.push_back((ts, digest));
+            *self.seen.entry(digest).or_default() += 1;
+        }
+
+        false
+    }
+
+    /// Drop the duplicates of an iterator, e.g. `LibPcap::read()`
+    pub fn dedup<I: Iterator<Item = Packet>>(self, packets: I) -> DedupIterator<I> {
+        DedupIterator { packets, dedup: self }
+    }
+
+    fn pop_front(&mut self) {
+        if let Some((_, digest)) = self.recent.pop_front() {
+            if let Some(count) = self.seen.get_mut(&digest) {
+                *count -= 1;
+
+                if *count == 0 {
+                    self.seen.remove(&digest);
+                }
+            }
+        }
+    }
+
+    fn digest(&self, datalink: DataLink, buf: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        if self.ignore_routing {
+            mask_routing(buf, datalink).hash(&mut hasher);
+        }
+        else {
+            buf.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+}
+
+
+impl Transform for Dedup {
+    fn apply(&mut self, _number: u64, pkt: Packet) -> Option<Packet> {
+        if self.is_duplicate(pkt.ts, &pkt.buf) { None } else { Some(pkt) }
+    }
+}
+
+
+/// Iterator over the packets left by a `Dedup`
+pub struct DedupIterator<I> {
+    packets: I,
+    dedup: Dedup,
+}
+
+
+impl<I> DedupIterator<I> {
+    /// Number of duplicates dropped so far
+    pub fn removed(&self) -> u64 {
+        self.dedup.removed
+    }
+}
+
+
+impl<I: Iterator<Item = Packet>> Iterator for DedupIterator<I> {
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dedup = &mut self.dedup;
+
+        self.packets.by_ref().find(|pkt| !dedup.is_duplicate(pkt.ts, &pkt.buf))
+    }
+}
+
+
+/// Packet with the fields changed by routing zeroed, VLAN tags and MAC addresses removed
+fn mask_routing(buf: &[u8], datalink: DataLink) -> Cow<'_, [u8]> {
+    let (mut value, ip) = match datalink {
+        DataLink::En10mb => {
+            let mut value = untagged(buf).into_owned();
+
+            if value.len() < ETHERNET_HEADER_LEN {
+                return Cow::Borrowed(buf);
+            }
+
+            value.drain(..12);
+            (value, 2)
+        },
+        DataLink::Raw => (buf.to_vec(), 0),
+        _ => return Cow::Borrowed(buf),
+    };
+
+    match value.get(ip).map(|v| v >> 4) {
+        Some(4) if value.len() >= ip + 12 => {
+            value[ip + 8] = 0;
+            value[ip + 10] = 0;
+            value[ip + 11] = 0;
+        },
+        Some(6) if value.len() >= ip + 8 => value[ip + 7] = 0,
+        _ => {},
+    }
+
+    Cow::Owned(value)
+}
+
+
+/// Chain of transforms applied to every packet in order
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::LibPcap;
+/// use libpcap_rs::edit::{Pipeline, SelectPackets, Truncate, TimeShift};
+///
+/// let mut input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+///
+/// let pipeline = Pipeline::new()
+///     .with(SelectPackets(1..=10))
+///     .with(Truncate(64))
+///     .with("-3600".parse::<TimeShift>().unwrap());
+///
+/// let packets: Vec<_> = pipeline.edit(input.read()).collect();
+///
+/// assert_eq!(packets.len(), 10);
+/// assert!(packets.iter().all(|v| v.caplen <= 64 && v.buf.len() == v.caplen as usize));
+/// ```
+///
+#[derive(Default)]
+pub struct Pipeline {
+    transforms: Vec<Box<dyn Transform>>,
+    number: u64,
+}
+
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a step at the end of the pipeline
+    pub fn with<T: Transform + 'static>(mut self, transform: T) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Number of packets given to the pipeline so far
+    pub fn count(&self) -> u64 {
+        self.number
+    }
+
+    /// Run the next input packet through every step, `None` if one of them dropped it
+    pub fn apply(&mut self, pkt: Packet) -> Option<Packet> {
+        self.number += 1;
+
+        self.transforms.iter_mut().try_fold(pkt, |pkt, transform| transform.apply(self.number, pkt))
+    }
+
+    /// Edit the packets of an iterator, e.g. `LibPcap::read()`
+    pub fn edit<I: Iterator<Item = Packet>>(self, packets: I) -> EditIterator<I> {
+        EditIterator { packets, pipeline: self }
+    }
+}
+
+
+/// Iterator over the packets left by a `Pipeline`
+pub struct EditIterator<I> {
+    packets: I,
+    pipeline: Pipeline,
+}
+
+
+impl<I> EditIterator<I> {
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+}
+
+
+impl<I: Iterator<Item = Packet>> Iterator for EditIterator<I> {
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for pkt in self.packets.by_ref() {
+            if let Some(pkt) = self.pipeline.apply(pkt) {
+                return Some(pkt);
+            }
+        }
+
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(sec: u64, buf: &[u8]) -> Packet {
+        Packet { timestamp: sec as i64, ts: Duration::from_secs(sec), caplen: buf.len() as u32, len: buf.len() as u32 + 10, buf: buf.to_vec() }
+    }
+
+    #[test]
+    fn test_edit_transforms() {
+        let packets = vec![packet(1, b"aaaa"), packet(2, b"bbbb"), packet(3, b"aaaa"), packet(4, b"cccc"), packet(5, b"aaaa")];
+
+        let value: Vec<_> = Pipeline::new()
+            .with(SelectTime { start: Some(Duration::from_secs(2)), end: None })
+            .with(Dedup::new(2))
+            .with(Truncate(2))
+            .with(TimeShift::Backward(Duration::from_secs(1)))
+            .edit(packets.into_iter())
+            .collect();
+
+        assert_eq!(value.iter().map(|v| v.ts.as_secs()).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(value[0].buf, b"bb");
+        assert_eq!((value[0].caplen, value[0].len), (2, 14));
+
+        let value: Vec<_> = Pipeline::new().with(SelectPackets(2..=3)).edit(value.into_iter()).collect();
+        assert_eq!(value.iter().map(|v| v.ts.as_secs()).collect::<Vec<_>>(), [2, 3]);
+
+        assert_eq!(parse_seconds("1084443427.311224"), Some(Duration::new(1084443427, 311224000)));
+        assert_eq!(parse_seconds(".5"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_seconds("1.2.3"), None);
+        assert!("abc".parse::<TimeShift>().is_err());
+    }
+
+    #[test]
+    fn test_dedup_ignore_routing() {
+        let input = b"\x00\xc0\x9f\x32\x41\x8c\x00\xe0\x18\xb1\x0c\xad\x08\x00\x45\x00\
+        \x00\x1c\x00\x00\x40\x00\x40\x11\x65\x47\xc0\xa8\xaa\x08\xc0\xa8\
+        \xaa\x14\x80\x1b\x00\x35\x00\x08\x85\xef";
+
+        // Routed copy: new MACs, VLAN tag, TTL - 1 and a new checksum
+        let mut routed = input[..12].iter().map(|v| v ^ 0xff).collect::<Vec<_>>();
+        routed.extend_from_slice(b"\x81\x00\x00\x0a");
+        routed.extend_from_slice(&input[12..]);
+        routed[4 + 22] -= 1;
+        routed[4 + 24] = 0x66;
+
+        let packets = || vec![packet(1, input), packet(2, &routed), packet(4, input)];
+
+        let mut dedup = Dedup::new(10).dedup(packets().into_iter());
+        assert_eq!(dedup.by_ref().count(), 2);
+        assert_eq!(dedup.removed(), 1);
+
+        let mut dedup = Dedup::new(10).with_ignore_routing(true).dedup(packets().into_iter());
+        assert_eq!(dedup.by_ref().count(), 1);
+        assert_eq!(dedup.removed(), 2);
+
+        let mut dedup = Dedup::with_window(DedupWindow::Time(Duration::from_secs(1)))
+            .with_ignore_routing(true)
+            .dedup(packets().into_iter());
+        assert_eq!(dedup.by_ref().map(|v| v.ts.as_secs()).collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(dedup.removed(), 1);
+    }
+}
@@ -1,3 +1,22 @@
+/// Reason of a `pcap_activate` or `pcap_set_*` failure, from the `PCAP_ERROR_*` code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcapErrorKind {
+    /// `PCAP_ERROR`
+    Generic,
+    /// The option can only be set before activation
+    Activated,
+    NoSuchDevice,
+    RfmonNotSupported,
+    NotRfmon,
+    PermissionDenied,
+    IfaceNotUp,
+    CantSetTstampType,
+    PromiscPermissionDenied,
+    TstampPrecisionNotSupported,
+    Other(i32),
+}
+
+
 /// libpcap error enum
 #[derive(Debug, thiserror::Error)]
 pub enum LibPcapError {
@@ -30,6 +49,80 @@ pub enum LibPcapError {
     FindAllDevsError {
         msg: String,
     },
+    #[error("lookupnet {iface:?} error: {msg:?}")]
+    LookUpNetError {
+        iface: String,
+        msg: String,
+    },
     #[error("send raw packet error")]
     SendRawPacketError,
+    #[error("send packet on {iface:?} error: {msg:?}")]
+    SendPacketError {
+        iface: String,
+        msg: String,
+    },
+    #[error("invalid data link: {name:?}")]
+    InvalidDataLink {
+        name: String,
+    },
+    #[error("list data links error: {msg:?}")]
+    ListDataLinksError {
+        msg: String,
+    },
+    #[error("invalid pcap header: {msg:?}")]
+    InvalidHeader {
+        msg: String,
+    },
+    #[error("invalid packet record: {msg:?}")]
+    InvalidRecord {
+        msg: String,
+    },
+    #[error("truncated record: {msg:?}")]
+    TruncatedRecord {
+        msg: String,
+    },
+    #[error("read error: {msg:?}")]
+    ReadError {
+        msg: String,
+    },
+    #[error("unsupported compression: {name:?}, enable its cargo feature")]
+    UnsupportedCompression {
+        name: String,
+    },
+    #[error("write error: {msg:?}")]
+    WriteError {
+        msg: String,
+    },
+    #[error("flush error: {msg:?}")]
+    FlushError {
+        msg: String,
+    },
+    #[error("set {option} error: {kind:?} - {msg:?}")]
+    SetOptionError {
+        option: String,
+        kind: PcapErrorKind,
+        msg: String,
+    },
+    #[error("activate error: {iface:?} - {kind:?} - {msg:?}")]
+    ActivateError {
+        iface: String,
+        kind: PcapErrorKind,
+        msg: String,
+    },
+    #[error("capture error: {msg:?}")]
+    CaptureError {
+        msg: String,
+    },
+    #[error("invalid time: {value:?}")]
+    InvalidTime {
+        value: String,
+    },
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("link type mismatch: {path:?} has {found}, expected {expected}")]
+    LinkTypeMismatch {
+        path: String,
+        expected: i32,
+        found: i32,
+    },
 }
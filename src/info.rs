@@ -0,0 +1,328 @@
+use std::fmt;
+use std::io::Read;
+use std::time::Duration;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::datalink::DataLink;
+use crate::compress::Compression;
+use crate::native::{CaptureReader, Endianness, PcapngBlock};
+use crate::packet::{FileFormat, TstampPrecision};
+use crate::path::join_home;
+use crate::stream::{InputStream, open_input, input_stream};
+use crate::time::strftime_utc;
+
+
+/// Summary of a capture file, like `capinfos`
+///
+/// The file is read once with the pure-Rust reader, so pcap and pcapng, compressed or not,
+/// are supported with or without the C libpcap. A damaged file is summarized up to the
+/// first unreadable packet, the error is kept in `error`.
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::info::CaptureInfo;
+///
+/// let info = CaptureInfo::open("tests/pcap/http_1.pcap").unwrap();
+///
+/// assert_eq!(info.packets, 43);
+/// assert!(info.time_ordered);
+///
+/// println!("{info}");
+/// println!("{}", info.to_json());
+/// ```
+///
+#[derive(Debug)]
+pub struct CaptureInfo {
+    /// Path of the file, `None` when read from a stream
+    pub path: Option<String>,
+    /// Size of the file on disk, compressed if it is
+    pub file_size: Option<u64>,
+    pub format: FileFormat,
+    pub compression: Compression,
+    pub endianness: Endianness,
+    pub version_major: u16,
+    pub version_minor: u16,
+    /// For pcapng the precision of the first interface
+    pub precision: TstampPrecision,
+    /// For pcapng the link type of the first interface
+    pub datalink: DataLink,
+    /// For pcapng the snapshot length of the first interface
+    pub snaplen: u32,
+    /// Number of pcapng interfaces over all the sections, 1 for pcap
+    pub interfaces: usize,
+    pub packets: u64,
+    /// Sum of the captured lengths
+    pub data_bytes: u64,
+    /// Sum of the original lengths
+    pub wire_bytes: u64,
+    /// Number of packets shorter than on the wire
+    pub truncated: u64,
+    pub first_ts: Option<Duration>,
+    pub last_ts: Option<Duration>,
+    /// Smallest captured length
+    pub min_size: Option<u32>,
+    /// Largest captured length
+    pub max_size: Option<u32>,
+    /// Whether every packet is at or after the previous one
+    pub time_ordered: bool,
+    /// Error that stopped the reading before the end of the file
+    pub error: Option<LibPcapError>,
+}
+
+
+impl CaptureInfo {
+    /// Summarize a capture file, `-` reads stdin
+    pub fn open(path: &str) -> PResult<Self> {
+        let input = open_input(path)?;
+        let mut info = Self::from_stream(input)?;
+
+        info.path = Some(path.to_string());
+        info.file_size = join_home(path).metadata().ok().filter(|v| v.is_file()).map(|v| v.len());
+
+        Ok(info)
+    }
+
+    /// Summarize a capture read from any stream, the compression is detected from the first bytes
+    pub fn from_reader<R: Read + Send + 'static>(reader: R) -> PResult<Self> {
+        Self::from_stream(input_stream(reader)?)
+    }
+
+    fn from_stream(input: InputStream) -> PResult<Self> {
+        let compression = input.compression();
+        let mut reader = CaptureReader::new(input)?;
+
+        let (endianness, version_major, version_minor, precision) = match &reader {
+            CaptureReader::Pcap(v) => {
+                let header = v.header();
+                (header.endianness, header.version_major, header.version_minor, header.precision)
+            },
+            CaptureReader::Pcapng(v) => {
+                let section = v.section();
+                let precision = match v.interfaces().first() {
+                    Some(iface) if iface.tsresol > 6 => TstampPrecision::Nano,
+                    _ => TstampPrecision::Micro,
+                };
+
+                (section.endianness, section.version_major, section.version_minor, precision)
+            },
+        };
+
+        let mut info = Self {
+            path: None,
+            file_size: None,
+            format: reader.format(),
+            compression,
+            endianness,
+            version_major,
+            version_minor,
+            precision,
+            datalink: reader.datalink(),
+            snaplen: reader.snaplen(),
+            interfaces: if matches!(reader, CaptureReader::Pcapng(_)) { 0 } else { 1 },
+            packets: 0,
+            data_bytes: 0,
+            wire_bytes: 0,
+            truncated: 0,
+            first_ts: None,
+            last_ts: None,
+            min_size: None,
+            max_size: None,
+            time_ordered: true,
+            error: None,
+        };
+
+        loop {
+            // The pcapng reader forgets the interfaces of a section at the next one, they are counted from their blocks
+            let next = match &mut reader {
+                CaptureReader::Pcap(v) => v.next_packet(),
+                CaptureReader::Pcapng(v) => match v.next_block() {
+                    Ok(Some(PcapngBlock::Packet(pkt))) => Ok(Some(pkt.packet)),
+                    Ok(Some(PcapngBlock::Interface(_))) => {
+                        info.interfaces += 1;
+                        continue;
+                    },
+                    Ok(Some(_)) => continue,
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                },
+            };
+
+            let pkt = match next {
+                Ok(Some(pkt)) => pkt,
+                Ok(None) => break,
+                Err(e) => {
+                    info.error = Some(e);
+                    break;
+                },
+            };
+
+            if info.last_ts.map(|v| pkt.ts < v).unwrap_or_default() {
+                info.time_ordered = false;
+            }
+
+            info.packets += 1;
+            info.data_bytes += pkt.caplen as u64;
+            info.wire_bytes += pkt.len as u64;
+            info.truncated += if pkt.caplen < pkt.len { 1 } else { 0 };
+            info.first_ts = Some(info.first_ts.map(|v| v.min(pkt.ts)).unwrap_or(pkt.ts));
+            info.last_ts = Some(info.last_ts.map(|v| v.max(pkt.ts)).unwrap_or(pkt.ts));
+            info.min_size = Some(info.min_size.map(|v| v.min(pkt.caplen)).unwrap_or(pkt.caplen));
+            info.max_size = Some(info.max_size.map(|v| v.max(pkt.caplen)).unwrap_or(pkt.caplen));
+        }
+
+        Ok(info)
+    }
+
+    /// Time between the first and the last packet
+    pub fn duration(&self) -> Option<Duration> {
+        Some(self.last_ts?.saturating_sub(self.first_ts?))
+    }
+
+    /// Average captured length
+    pub fn avg_size(&self) -> Option<f64> {
+        if self.packets == 0 { None } else { Some(self.data_bytes as f64 / self.packets as f64) }
+    }
+
+    /// Captured bytes per second, `None` when the capture lasts no time
+    pub fn byte_rate(&self) -> Option<f64> {
+        self.per_second(self.data_bytes)
+    }
+
+    /// Captured bits per second
+    pub fn bit_rate(&self) -> Option<f64> {
+        self.byte_rate().map(|v| v * 8.0)
+    }
+
+    /// Packets per second
+    pub fn packet_rate(&self) -> Option<f64> {
+        self.per_second(self.packets)
+    }
+
+    fn per_second(&self, value: u64) -> Option<f64> {
+        let secs = self.duration()?.as_secs_f64();
+
+        if secs > 0.0 { Some(value as f64 / secs) } else { None }
+    }
+
+    fn format_ts(&self, ts: Duration) -> String {
+        match self.precision {
+            TstampPrecision::Micro => format!("{}.{:06}", ts.as_secs(), ts.subsec_micros()),
+            TstampPrecision::Nano => format!("{}.{:09}", ts.as_secs(), ts.subsec_nanos()),
+        }
+    }
+
+    /// Single line JSON object, missing values are `null`
+    pub fn to_json(&self) -> String {
+        let string = |v: Option<String>| v.map(|v| format!("\"{}\"", json_escape(&v))).unwrap_or_else(|| "null".to_string());
+        let number = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        let fields = [
+            ("path", string(self.path.clone())),
+            ("file_size", number(self.file_size.map(|v| v.to_string()))),
+            ("format", string(Some(self.format.to_string()))),
+            ("compression", string(Some(self.compression.to_string()))),
+            ("endianness", string(Some(endianness_name(self.endianness).to_string()))),
+            ("version", string(Some(format!("{}.{}", self.version_major, self.version_minor)))),
+            ("tstamp_precision", string(Some(self.precision.to_string()))),
+            ("linktype", self.datalink.value().to_string()),
+            ("encapsulation", string(self.datalink.name())),
+            ("snaplen", self.snaplen.to_string()),
+            ("interfaces", self.interfaces.to_string()),
+            ("packets", self.packets.to_string()),
+            ("data_bytes", self.data_bytes.to_string()),
+            ("wire_bytes", self.wire_bytes.to_string()),
+            ("truncated", self.truncated.to_string()),
+            ("first_ts", number(self.first_ts.map(|v| self.format_ts(v)))),
+            ("last_ts", number(self.last_ts.map(|v| self.format_ts(v)))),
+            ("duration", number(self.duration().map(|v| self.format_ts(v)))),
+            ("byte_rate", number(self.byte_rate().map(|v| format!("{v:.3}")))),
+            ("bit_rate", number(self.bit_rate().map(|v| format!("{v:.3}")))),
+            ("packet_rate", number(self.packet_rate().map(|v| format!("{v:.3}")))),
+            ("min_size", number(self.min_size.map(|v| v.to_string()))),
+            ("max_size", number(self.max_size.map(|v| v.to_string()))),
+            ("avg_size", number(self.avg_size().map(|v| format!("{v:.3}")))),
+            ("time_ordered", self.time_ordered.to_string()),
+            ("error", string(self.error.as_ref().map(|v| v.to_string()))),
+        ];
+
+        let fields: Vec<String> = fields.iter().map(|(k, v)| format!("\"{k}\": {v}")).collect();
+
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+
+impl fmt::Display for CaptureInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = || "n/a".to_string();
+        let time = |v: Option<Duration>| {
+            v.map(|v| format!("{} UTC ({})", strftime_utc("%Y-%m-%d %H:%M:%S", v), self.format_ts(v))).unwrap_or_else(unknown)
+        };
+        let rate = |v: Option<f64>, unit: &str| v.map(|v| format!("{v:.2} {unit}")).unwrap_or_else(unknown);
+
+        if let Some(path) = &self.path {
+            writeln!(f, "File name:           {path}")?;
+        }
+
+        if let Some(size) = self.file_size {
+            writeln!(f, "File size:           {size} bytes")?;
+        }
+
+        writeln!(f, "File type:           {} {}.{}, {} endian", self.format, self.version_major, self.version_minor, endianness_name(self.endianness))?;
+        writeln!(f, "Compression:         {}", self.compression)?;
+        writeln!(f, "File encapsulation:  {} ({})", self.datalink.description().or_else(|| self.datalink.name()).unwrap_or_else(unknown), self.datalink.value())?;
+        writeln!(f, "Time precision:      {}", self.precision)?;
+        writeln!(f, "Snapshot length:     {}", self.snaplen)?;
+        writeln!(f, "Interfaces:          {}", self.interfaces)?;
+        writeln!(f, "Number of packets:   {}", self.packets)?;
+        writeln!(f, "Data size:           {} bytes", self.data_bytes)?;
+        writeln!(f, "Wire size:           {} bytes", self.wire_bytes)?;
+        writeln!(f, "Truncated packets:   {}", self.truncated)?;
+        writeln!(f, "First packet time:   {}", time(self.first_ts))?;
+        writeln!(f, "Last packet time:    {}", time(self.last_ts))?;
+        writeln!(f, "Capture duration:    {}", self.duration().map(|v| format!("{} seconds", self.format_ts(v))).unwrap_or_else(unknown))?;
+        writeln!(f, "Data byte rate:      {}", rate(self.byte_rate(), "bytes/s"))?;
+        writeln!(f, "Data bit rate:       {}", rate(self.bit_rate(), "bits/s"))?;
+        writeln!(f, "Average packet rate: {}", rate(self.packet_rate(), "packets/s"))?;
+        writeln!(f, "Packet size:         min {}, max {}, avg {}",
+            self.min_size.map(|v| v.to_string()).unwrap_or_else(unknown),
+            self.max_size.map(|v| v.to_string()).unwrap_or_else(unknown),
+            self.avg_size().map(|v| format!("{v:.2}")).unwrap_or_else(unknown),
+        )?;
+        write!(f, "Strict time order:   {}", if self.time_ordered { "True" } else { "False" })?;
+
+        if let Some(e) = &self.error {
+            write!(f, "\nRead error:          {e}")?;
+        }
+
+        Ok(())
+    }
+}
+
+
+fn endianness_name(value: Endianness) -> &'static str {
+    match value {
+        Endianness::Little => "little",
+        Endianness::Big => "big",
+    }
+}
+
+
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,278 @@
+//! Network interfaces reported by `pcap_findalldevs`.
+use std::fmt;
+use std::ffi::{CStr, CString};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::make_cstr;
+use crate::libpcap::{
+    pcap_if_t, pcap_addr, sockaddr, bpf_u_int32,
+    PCAP_ERRBUF_SIZE, PCAP_IF_LOOPBACK, PCAP_IF_UP, PCAP_IF_RUNNING,
+    pcap_findalldevs, pcap_freealldevs, pcap_lookupnet,
+};
+
+
+// Not in the headers of libpcap before 1.9
+pub const PCAP_IF_WIRELESS: u32 = 0x0000_0008;
+pub const PCAP_IF_CONNECTION_STATUS: u32 = 0x0000_0030;
+pub const PCAP_IF_CONNECTION_STATUS_UNKNOWN: u32 = 0x0000_0000;
+pub const PCAP_IF_CONNECTION_STATUS_CONNECTED: u32 = 0x0000_0010;
+pub const PCAP_IF_CONNECTION_STATUS_DISCONNECTED: u32 = 0x0000_0020;
+pub const PCAP_IF_CONNECTION_STATUS_NOT_APPLICABLE: u32 = 0x0000_0030;
+
+
+/// Whether the interface is plugged in, or associated for a wireless one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Unknown,
+    Connected,
+    Disconnected,
+    /// No notion of connection, eg: loopback
+    NotApplicable,
+}
+
+
+/// IPv4 or IPv6 address of an interface, the other address families are left out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddress {
+    pub addr: IpAddr,
+    pub netmask: Option<IpAddr>,
+    pub broadcast: Option<IpAddr>,
+    /// Other end of a point-to-point link
+    pub destination: Option<IpAddr>,
+}
+
+
+/// Network interface, see `get_interfaces`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub description: Option<String>,
+    pub addresses: Vec<InterfaceAddress>,
+    /// `PCAP_IF_*` flags
+    pub flags: u32,
+}
+
+
+impl Interface {
+    #[inline]
+    pub fn is_loopback(&self) -> bool {
+        self.flags & PCAP_IF_LOOPBACK != 0
+    }
+
+    #[inline]
+    pub fn is_up(&self) -> bool {
+        self.flags & PCAP_IF_UP != 0
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.flags & PCAP_IF_RUNNING != 0
+    }
+
+    #[inline]
+    pub fn is_wireless(&self) -> bool {
+        self.flags & PCAP_IF_WIRELESS != 0
+    }
+
+    pub fn connection_status(&self) -> ConnectionStatus {
+        match self.flags & PCAP_IF_CONNECTION_STATUS {
+            PCAP_IF_CONNECTION_STATUS_CONNECTED => ConnectionStatus::Connected,
+            PCAP_IF_CONNECTION_STATUS_DISCONNECTED => ConnectionStatus::Disconnected,
+            PCAP_IF_CONNECTION_STATUS_NOT_APPLICABLE => ConnectionStatus::NotApplicable,
+            _ => ConnectionStatus::Unknown,
+        }
+    }
+
+    /// Up, running and not loopback, the interface a capture is expected on
+    pub fn is_usable(&self) -> bool {
+        self.is_up() && self.is_running() && !self.is_loopback()
+    }
+
+    /// IPv4 network number and netmask of the interface, see `lookup_net`
+    pub fn lookup_net(&self) -> PResult<(Ipv4Addr, Ipv4Addr)> {
+        lookup_net(&self.name)
+    }
+
+    unsafe fn from_raw(value: &pcap_if_t) -> Self {
+        let description = if value.description.is_null() { None } else { Some(make_cstr!(value.description)) };
+        let mut addresses = vec![];
+        let mut addr = value.addresses;
+
+        while !addr.is_null() {
+            if let Some(v) = InterfaceAddress::from_raw(&*addr) {
+                addresses.push(v);
+            }
+
+            addr = (*addr).next;
+        }
+
+        Self {
+            name: make_cstr!(value.name),
+            description,
+            addresses,
+            flags: value.flags,
+        }
+    }
+}
+
+
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if let Some(description) = &self.description {
+            write!(f, " ({description})")?;
+        }
+
+        let mut flags = vec![];
+
+        if self.is_up() { flags.push("UP") }
+        if self.is_running() { flags.push("RUNNING") }
+        if self.is_loopback() { flags.push("LOOPBACK") }
+        if self.is_wireless() { flags.push("WIRELESS") }
+
+        match self.connection_status() {
+            ConnectionStatus::Connected => flags.push("CONNECTED"),
+            ConnectionStatus::Disconnected => flags.push("DISCONNECTED"),
+            _ => {},
+        }
+
+        write!(f, " [{}]", flags.join(","))?;
+
+        for addr in &self.addresses {
+            write!(f, "\n    {}", addr.addr)?;
+
+            if let Some(v) = addr.netmask { write!(f, " netmask {v}")?; }
+            if let Some(v) = addr.broadcast { write!(f, " broadcast {v}")?; }
+            if let Some(v) = addr.destination { write!(f, " destination {v}")?; }
+        }
+
+        Ok(())
+    }
+}
+
+
+impl InterfaceAddress {
+    unsafe fn from_raw(value: &pcap_addr) -> Option<Self> {
+        let addr = sockaddr_to_ip(value.addr)?;
+
+        Some(Self {
+            addr,
+            netmask: sockaddr_to_ip(value.netmask),
+            broadcast: sockaddr_to_ip(value.broadaddr),
+            destination: sockaddr_to_ip(value.dstaddr),
+        })
+    }
+}
+
+
+/// `sockaddr` is opaque in the bindings, read it through the libc layouts
+unsafe fn sockaddr_to_ip(value: *const sockaddr) -> Option<IpAddr> {
+    if value.is_null() {
+        return None;
+    }
+
+    let value = value as *const libc::sockaddr;
+
+    match (*value).sa_family as i32 {
+        libc::AF_INET => {
+            let v = &*(value as *const libc::sockaddr_in);
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(v.sin_addr.s_addr))))
+        },
+        libc::AF_INET6 => {
+            let v = &*(value as *const libc::sockaddr_in6);
+            Some(IpAddr::V6(Ipv6Addr::from(v.sin6_addr.s6_addr)))
+        },
+        _ => None,
+    }
+}
+
+
+/// Obtain the network interfaces with their addresses and flags
+///
+/// libpcap lists the interfaces up, running and with addresses first.
+///
+/// # Returns:
+///
+/// - `PResult<Vec<Interface>>`
+///
+/// # Examples:
+///
+/// ```rust
+/// use libpcap_rs::get_interfaces;
+///
+/// for iface in get_interfaces().unwrap_or_default() {
+///     println!("{iface}");
+/// }
+/// ```
+///
+pub fn get_interfaces() -> PResult<Vec<Interface>> {
+    let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
+    let mut interfaces = std::ptr::null_mut();
+    let mut interface_list = vec![];
+
+    let ret = unsafe { pcap_findalldevs(&mut interfaces, errbuf.as_mut_ptr()) };
+
+    if ret == -1 {
+        return Err(LibPcapError::FindAllDevsError {
+            msg: make_cstr!(errbuf.as_ptr()),
+        })
+    }
+
+    let mut interface_temp = interfaces;
+
+    while !interface_temp.is_null() {
+        unsafe {
+            interface_list.push(Interface::from_raw(&*interface_temp));
+            interface_temp = (*interface_temp).next;
+        }
+    }
+
+    if !interfaces.is_null() {
+        unsafe { pcap_freealldevs(interfaces) };
+    }
+
+    Ok(interface_list)
+}
+
+
+/// Obtain the IPv4 network number and netmask of a network interface
+///
+/// # Args:
+///
+/// - `iface: &str`: network port name
+///
+/// # Returns:
+///
+/// - `PResult<(Ipv4Addr, Ipv4Addr)>`: network number and netmask
+///
+/// # Examples:
+///
+/// ```rust
+/// use libpcap_rs::interface::lookup_net;
+///
+/// println!("{:?}", lookup_net("lo"))
+/// ```
+///
+pub fn lookup_net(iface: &str) -> PResult<(Ipv4Addr, Ipv4Addr)> {
+    let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
+    let mut net: bpf_u_int32 = 0;
+    let mut mask: bpf_u_int32 = 0;
+    let device = CString::new(iface).map_err(|_| LibPcapError::LookUpNetError {
+        iface: iface.to_string(),
+        msg: "interface name contains a NUL byte".to_string(),
+    })?;
+
+    let ret = unsafe { pcap_lookupnet(device.as_ptr(), &mut net, &mut mask, errbuf.as_mut_ptr()) };
+
+    if ret == -1 {
+        return Err(LibPcapError::LookUpNetError {
+            iface: iface.to_string(),
+            msg: make_cstr!(errbuf.as_ptr()),
+        })
+    }
+
+    // Both in network byte order
+    Ok((Ipv4Addr::from(u32::from_be(net)), Ipv4Addr::from(u32::from_be(mask))))
+}
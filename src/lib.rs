@@ -1,19 +1,47 @@
+#[cfg(feature = "libpcap")]
 #[allow(non_camel_case_types)]
 #[allow(non_snake_case)]
 #[allow(non_upper_case_globals)]
 pub mod libpcap;
+#[cfg(feature = "libpcap")]
 pub mod wrapper;
 pub mod errors;
+#[cfg(feature = "libpcap")]
+pub mod interface;
+#[cfg(feature = "libpcap")]
 pub mod sniff;
+#[cfg(all(feature = "async", unix))]
+pub mod sniff_stream;
 pub mod traits;
 pub(crate) mod time;
 mod utils;
 mod ffi;
 pub mod path;
 pub mod net;
+pub mod packet;
+pub mod datalink;
+pub mod native;
+pub mod compress;
+pub mod stream;
+pub mod rotate;
+pub mod merge;
+pub mod split;
+pub mod edit;
+pub mod info;
+pub mod stats;
 
+#[cfg(feature = "libpcap")]
 pub use wrapper::{LibPcap, get_first_iface, get_iface_list, send_packet};
+#[cfg(feature = "libpcap")]
+pub use interface::{Interface, get_interfaces};
+#[cfg(not(feature = "libpcap"))]
+pub use native::LibPcap;
+#[cfg(feature = "libpcap")]
 pub use sniff::Sniff;
+#[cfg(all(feature = "async", unix))]
+pub use sniff_stream::SniffStream;
+pub use datalink::DataLink;
+pub use packet::Packet;
 pub use errors::LibPcapError;
 pub use net::*;
 
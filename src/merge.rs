@@ -0,0 +1,163 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::Duration;
+use jkcenum::JkcEnum;
+use crate::packet::Packet;
+
+
+/// How the packets of several captures are merged
+#[derive(Debug, Default, Clone, Copy, JkcEnum, PartialEq, Eq)]
+pub enum MergeMode {
+    /// Packets in timestamp order, like `mergecap`
+    #[default]
+    #[jenum(rename="time")]
+    Time,
+    /// Inputs one after another, like `mergecap -a`
+    #[jenum(rename="concat")]
+    Concat,
+}
+
+
+/// Streaming k-way merge of packet iterators
+///
+/// Only the next packet of every input is kept in memory, whatever the size of the inputs.
+/// Each input is expected to be in timestamp order. Packets with equal timestamps keep
+/// the order of their inputs, then their order within an input.
+///
+/// Yields the index of the input each packet comes from with the packet.
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::LibPcap;
+/// use libpcap_rs::merge::{MergeIterator, MergeMode};
+///
+/// let mut inputs = [
+///     LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap(),
+///     LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap(),
+/// ];
+///
+/// let mut last = std::time::Duration::ZERO;
+///
+/// for (_, pkt) in MergeIterator::new(inputs.iter_mut().map(|v| v.read()), MergeMode::Time) {
+///     assert!(pkt.ts >= last);
+///     last = pkt.ts;
+/// }
+/// ```
+///
+pub struct MergeIterator<I: Iterator<Item = Packet>> {
+    inputs: Vec<I>,
+    mode: MergeMode,
+    /// Next packet of every input
+    heads: Vec<Option<Packet>>,
+    /// `(timestamp, input)` of the heads, the smallest on top
+    queue: BinaryHeap<Reverse<(Duration, usize)>>,
+    /// Input being read in `MergeMode::Concat`
+    current: usize,
+}
+
+
+impl<I: Iterator<Item = Packet>> MergeIterator<I> {
+    /// # Args:
+    ///
+    /// - `inputs`: packet iterators, e.g. `LibPcap::read()` of every file
+    /// - `mode`: timestamp order or concatenation
+    ///
+    pub fn new<T: IntoIterator<Item = I>>(inputs: T, mode: MergeMode) -> Self {
+        let mut inputs: Vec<I> = inputs.into_iter().collect();
+        let mut heads = Vec::with_capacity(inputs.len());
+        let mut queue = BinaryHeap::with_capacity(inputs.len());
+
+        if mode == MergeMode::Time {
+            for (i, input) in inputs.iter_mut().enumerate() {
+                let head = input.next();
+
+                if let Some(pkt) = &head {
+                    queue.push(Reverse((pkt.ts, i)));
+                }
+
+                heads.push(head);
+            }
+        }
+
+        Self { inputs, mode, heads, queue, current: 0 }
+    }
+
+    pub fn mode(&self) -> MergeMode {
+        self.mode
+    }
+
+    /// Number of merged inputs
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Give back the inputs, e.g. to check their errors
+    pub fn into_inner(self) -> Vec<I> {
+        self.inputs
+    }
+}
+
+
+impl<I: Iterator<Item = Packet>> Iterator for MergeIterator<I> {
+    type Item = (usize, Packet);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.mode {
+            MergeMode::Time => {
+                let Reverse((_, i)) = self.queue.pop()?;
+                let pkt = self.heads[i].take()?;
+
+                self.heads[i] = self.inputs[i].next();
+
+                if let Some(next) = &self.heads[i] {
+                    self.queue.push(Reverse((next.ts, i)));
+                }
+
+                Some((i, pkt))
+            },
+            MergeMode::Concat => {
+                while let Some(input) = self.inputs.get_mut(self.current) {
+                    if let Some(pkt) = input.next() {
+                        return Some((self.current, pkt));
+                    }
+
+                    self.current += 1;
+                }
+
+                None
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(input: &[(u64, u8)]) -> std::vec::IntoIter<Packet> {
+        input.iter().map(|(ts, v)| {
+            Packet { timestamp: *ts as i64, ts: Duration::from_secs(*ts), caplen: 1, len: 1, buf: vec![*v] }
+        }).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_merge_stable() {
+        let inputs = || vec![
+            packets(&[(1, 0), (3, 1), (3, 2), (9, 3)]),
+            packets(&[]),
+            packets(&[(2, 10), (3, 11), (4, 12)]),
+        ];
+
+        let value: Vec<_> = MergeIterator::new(inputs(), MergeMode::Time).map(|(i, v)| (i, v.buf[0])).collect();
+        assert_eq!(value, [(0, 0), (2, 10), (0, 1), (0, 2), (2, 11), (2, 12), (0, 3)]);
+
+        let value: Vec<_> = MergeIterator::new(inputs(), MergeMode::Concat).map(|(i, v)| (i, v.buf[0])).collect();
+        assert_eq!(value, [(0, 0), (0, 1), (0, 2), (0, 3), (2, 10), (2, 11), (2, 12)]);
+    }
+}
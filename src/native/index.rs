@@ -0,0 +1,498 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::path::join_home;
+use crate::packet::Packet;
+use super::pcap::{PcapHeader, PcapReader, PCAP_MAX_CAPLEN, PCAP_RECORD_HEADER_LEN, read_full};
+
+
+/// Magic number of an index sidecar file
+pub const INDEX_MAGIC: [u8; 4] = *b"PIDX";
+pub const INDEX_VERSION: u32 = 1;
+/// Extension appended to the capture path for the sidecar file
+pub const INDEX_EXTENSION: &str = "idx";
+const INDEX_HEADER_LEN: usize = 24;
+const INDEX_ENTRY_LEN: usize = 20;
+
+
+/// Position and timestamp of one packet record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Byte offset of the record header in the capture
+    pub offset: u64,
+    pub ts: Duration,
+}
+
+
+/// Offset/timestamp index of a classic pcap capture
+///
+/// The sidecar file stores the magic `PIDX`, a version, the capture length, the packet count,
+/// then one little-endian `offset: u64, sec: u64, nsec: u32` entry per packet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PcapIndex {
+    capture_len: u64,
+    entries: Vec<IndexEntry>,
+    sorted: bool,
+}
+
+
+impl PcapIndex {
+    /// Scan every record of a pcap stream
+    ///
+    /// The stream must be positioned right after the file header, as left by `PcapReader::new`.
+    pub fn build<R: Read + Seek>(reader: &mut PcapReader<R>) -> PResult<Self> {
+        let header = reader.header().clone();
+        let stream = reader.get_mut();
+
+        let mut offset = stream.stream_position()?;
+        let capture_len = stream.seek(SeekFrom::End(0))?;
+        stream.seek(SeekFrom::Start(offset))?;
+
+        let mut entries = vec![];
+
+        loop {
+            let mut record = [0; PCAP_RECORD_HEADER_LEN];
+
+            match read_full(stream, &mut record)? {
+                0 => break,
+                PCAP_RECORD_HEADER_LEN => {},
+                n => return Err(LibPcapError::TruncatedRecord {
+                    msg: format!("tried to read {PCAP_RECORD_HEADER_LEN} header bytes, only got {n}"),
+                }),
+            }
+
+            let e = header.endianness;
+            let sec = e.read_u32(&record[0..]);
+            let frac = e.read_u32(&record[4..]);
+            let caplen = e.read_u32(&record[8..]);
+
+            if caplen > PCAP_MAX_CAPLEN {
+                return Err(LibPcapError::InvalidRecord { msg: format!("caplen {caplen} is too large") });
+            }
+
+            let n = std::io::copy(&mut stream.by_ref().take(caplen as u64), &mut std::io::sink())?;
+
+            if n != caplen as u64 {
+                return Err(LibPcapError::TruncatedRecord {
+                    msg: format!("tried to read {caplen} captured bytes, only got {n}"),
+                });
+            }
+
+            entries.push(IndexEntry {
+                offset,
+                ts: header.precision.to_duration(sec as u64, frac),
+            });
+
+            offset += (PCAP_RECORD_HEADER_LEN as u64) + n;
+        }
+
+        Ok(Self::from_entries(capture_len, entries))
+    }
+
+    fn from_entries(capture_len: u64, entries: Vec<IndexEntry>) -> Self {
+        let sorted = entries.windows(2).all(|v| v[0].ts <= v[1].ts);
+
+        Self {
+            capture_len,
+            entries,
+            sorted,
+        }
+    }
+
+    /// Path of the sidecar file of a capture, `<path>.idx`
+    pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut value = path.as_ref().as_os_str().to_owned();
+        value.push(".");
+        value.push(INDEX_EXTENSION);
+
+        PathBuf::from(value)
+    }
+
+    /// Load an index written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> PResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; INDEX_HEADER_LEN];
+        let invalid = |msg: &str| LibPcapError::InvalidHeader { msg: msg.to_string() };
+
+        if read_full(&mut reader, &mut header)? != INDEX_HEADER_LEN || header[..4] != INDEX_MAGIC {
+            return Err(invalid("not a pcap index file"));
+        }
+
+        if u32::from_le_bytes([header[4], header[5], header[6], header[7]]) != INDEX_VERSION {
+            return Err(invalid("unsupported pcap index version"));
+        }
+
+        let capture_len = u64::from_le_bytes(header[8..16].try_into().unwrap_or_default());
+        let count = u64::from_le_bytes(header[16..24].try_into().unwrap_or_default());
+        let mut entries = vec![];
+        let mut entry = [0; INDEX_ENTRY_LEN];
+
+        for _ in 0..count {
+            if read_full(&mut reader, &mut entry)? != INDEX_ENTRY_LEN {
+                return Err(invalid("pcap index file is truncated"));
+            }
+
+            let offset = u64::from_le_bytes(entry[0..8].try_into().unwrap_or_default());
+            let sec = u64::from_le_bytes(entry[8..16].try_into().unwrap_or_default());
+            let nsec = u32::from_le_bytes(entry[16..20].try_into().unwrap_or_default());
+
+            entries.push(IndexEntry { offset, ts: Duration::new(sec, nsec) });
+        }
+
+        Ok(Self::from_entries(capture_len, entries))
+    }
+
+    /// Persist the index
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> PResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(&INDEX_MAGIC)?;
+        writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+        writer.write_all(&self.capture_len.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+
+        for entry in &self.entries {
+            writer.write_all(&entry.offset.to_le_bytes())?;
+            writer.write_all(&entry.ts.as_secs().to_le_bytes())?;
+            writer.write_all(&entry.ts.subsec_nanos().to_le_bytes())?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Length in bytes of the indexed capture, used to detect a stale sidecar
+    pub fn capture_len(&self) -> u64 {
+        self.capture_len
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether the timestamps never go backwards, time lookups are then binary searches
+    pub fn is_sorted(&self) -> bool {
+        self.sorted
+    }
+
+    /// Number of the first packet, in file order, whose timestamp is at least `ts`
+    pub fn position_of_time(&self, ts: Duration) -> usize {
+        if self.sorted {
+            self.entries.partition_point(|v| v.ts < ts)
+        }
+        else {
+            self.entries.iter().position(|v| v.ts >= ts).unwrap_or(self.entries.len())
+        }
+    }
+}
+
+
+/// Classic pcap reader with random access through a `PcapIndex`
+///
+/// # Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use libpcap_rs::native::IndexedReader;
+///
+/// let mut reader = IndexedReader::open("tests/pcap/http_1.pcap").unwrap();
+///
+/// let pkt = reader.get(10).unwrap().unwrap();
+/// println!("packet #10 {:?} {}", pkt.ts, pkt.caplen);
+///
+/// let start = Duration::new(1084443428, 0);
+///
+/// for pkt in reader.time_range(start, start + Duration::from_secs(1)) {
+///     println!("{:?}", pkt.unwrap().ts);
+/// }
+/// ```
+///
+pub struct IndexedReader<R> {
+    reader: PcapReader<R>,
+    index: PcapIndex,
+    position: usize,
+    /// Offset the underlying reader is at, when known
+    cursor: Option<u64>,
+}
+
+
+impl IndexedReader<BufReader<File>> {
+    /// Open a pcap file and index it in memory
+    pub fn open(path: &str) -> PResult<Self> {
+        Self::new(Self::open_file(path)?)
+    }
+
+    /// Open a pcap file, reusing its sidecar index or writing one if it is missing or stale
+    ///
+    /// A sidecar is stale when its recorded capture length differs from the file length, a file
+    /// rewritten to the same length keeps its old index. Writing the sidecar is best effort, the
+    /// file is still opened with the in-memory index in a read-only directory.
+    pub fn open_with_sidecar(path: &str) -> PResult<Self> {
+        let reader = Self::open_file(path)?;
+        let sidecar = PcapIndex::sidecar_path(join_home(path));
+        let capture_len = reader.get_ref().metadata()?.len();
+
+        if let Ok(index) = PcapIndex::load(&sidecar) {
+            if index.capture_len() == capture_len {
+                return Self::with_index(reader, index);
+            }
+        }
+
+        let value = Self::new(reader)?;
+        let _ = value.index.save(&sidecar);
+
+        Ok(value)
+    }
+
+    fn open_file(path: &str) -> PResult<BufReader<File>> {
+        let pathobj = join_home(path);
+
+        if !pathobj.is_file() {
+            return Err(LibPcapError::FileNotExtists { path: path.to_string() });
+        }
+
+        Ok(BufReader::new(File::open(pathobj)?))
+    }
+}
+
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// Read the file header and index every record
+    pub fn new(reader: R) -> PResult<Self> {
+        let mut reader = PcapReader::new(reader)?;
+        let index = PcapIndex::build(&mut reader)?;
+
+        Ok(Self {
+            reader,
+            index,
+            position: 0,
+            cursor: None,
+        })
+    }
+
+    /// Read the file header and use an index built earlier for the same capture
+    pub fn with_index(reader: R, index: PcapIndex) -> PResult<Self> {
+        Ok(Self {
+            reader: PcapReader::new(reader)?,
+            index,
+            position: 0,
+            cursor: None,
+        })
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        self.reader.header()
+    }
+
+    pub fn index(&self) -> &PcapIndex {
+        &self.index
+    }
+
+    /// Number of packets in the capture
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Number of the packet returned by the next `next_packet`
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Move to packet number `n`, past the end is allowed and reads nothing
+    pub fn seek(&mut self, n: usize) {
+        self.position = n.min(self.index.len());
+    }
+
+    /// Move to the first packet whose timestamp is at least `ts` and return its number
+    pub fn seek_to_time(&mut self, ts: Duration) -> usize {
+        self.position = self.index.position_of_time(ts);
+        self.position
+    }
+
+    /// Read the packet at the current position and move to the next one
+    pub fn next_packet(&mut self) -> PResult<Option<Packet>> {
+        let pkt = self.get(self.position)?;
+
+        if pkt.is_some() {
+            self.position += 1;
+        }
+
+        Ok(pkt)
+    }
+
+    /// Read packet number `n`, `Ok(None)` past the end
+    pub fn get(&mut self, n: usize) -> PResult<Option<Packet>> {
+        let entry = match self.index.entries.get(n) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let offset = entry.offset;
+
+        // Seeking drops the read buffer, packets read in a row don't need it
+        if self.cursor.take() != Some(offset) {
+            self.reader.get_mut().seek(SeekFrom::Start(offset))?;
+        }
+
+        match self.reader.next_packet()? {
+            Some(pkt) => {
+                self.cursor = Some(offset + PCAP_RECORD_HEADER_LEN as u64 + pkt.buf.len() as u64);
+                Ok(Some(pkt))
+            },
+            None => Err(LibPcapError::TruncatedRecord { msg: format!("packet {n} is missing, the index is stale") }),
+        }
+    }
+
+    /// Iterate over packets by number
+    pub fn range(&mut self, range: Range<usize>) -> IndexedIterator<'_, R> {
+        let end = range.end.min(self.index.len());
+
+        IndexedIterator {
+            reader: self,
+            next: range.start,
+            end,
+            time: None,
+        }
+    }
+
+    /// Iterate over packets with `start <= ts < end`, in file order
+    pub fn time_range(&mut self, start: Duration, end: Duration) -> IndexedIterator<'_, R> {
+        let next = self.index.position_of_time(start);
+        let count = self.index.len();
+
+        IndexedIterator {
+            reader: self,
+            next,
+            end: count,
+            time: Some((start, end)),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+
+/// Iterator over a range of an `IndexedReader`
+pub struct IndexedIterator<'a, R> {
+    reader: &'a mut IndexedReader<R>,
+    next: usize,
+    end: usize,
+    time: Option<(Duration, Duration)>,
+}
+
+
+impl<'a, R: Read + Seek> Iterator for IndexedIterator<'a, R> {
+    type Item = PResult<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.end {
+            let n = self.next;
+            self.next += 1;
+
+            if let Some((start, end)) = self.time {
+                let ts = self.reader.index.entries[n].ts;
+
+                if ts >= end && self.reader.index.sorted {
+                    self.next = self.end;
+                    return None;
+                }
+
+                if ts < start || ts >= end {
+                    continue;
+                }
+            }
+
+            return self.reader.get(n).transpose();
+        }
+
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datalink::DataLink;
+    use crate::packet::TstampPrecision;
+    use crate::native::pcap::PcapWriter;
+
+    #[test]
+    fn test_index_unsorted() {
+        let header = PcapHeader::new(DataLink::Raw, 65535, TstampPrecision::Nano);
+        let mut writer = PcapWriter::new(Vec::new(), header).unwrap();
+
+        for sec in [3, 1, 4, 1, 5] {
+            writer.write_packet(&[sec as u8; 4], Duration::new(sec, 7), 4).unwrap();
+        }
+
+        let mut reader = IndexedReader::new(std::io::Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(reader.len(), 5);
+        assert!(!reader.index().is_sorted());
+
+        assert_eq!(reader.get(2).unwrap().unwrap().buf, vec![4; 4]);
+        assert_eq!(reader.get(5).unwrap(), None);
+
+        let values: Vec<_> = reader.time_range(Duration::from_secs(1), Duration::from_secs(4))
+            .map(|v| v.unwrap().ts.as_secs())
+            .collect();
+        assert_eq!(values, vec![3, 1, 1]);
+
+        assert_eq!(reader.seek_to_time(Duration::from_secs(4)), 2);
+        assert_eq!(reader.next_packet().unwrap().unwrap().ts, Duration::new(4, 7));
+        assert_eq!(reader.position(), 3);
+    }
+
+    /// Cursor counting its seeks
+    struct Seeks(std::io::Cursor<Vec<u8>>, usize);
+
+    impl Read for Seeks {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for Seeks {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.1 += 1;
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_index_sequential_reads() {
+        let header = PcapHeader::new(DataLink::Raw, 65535, TstampPrecision::Micro);
+        let mut writer = PcapWriter::new(Vec::new(), header).unwrap();
+
+        for sec in 0..10 {
+            writer.write_packet(&[sec as u8; 4], Duration::from_secs(sec), 4).unwrap();
+        }
+
+        let data = writer.into_inner();
+        let index = PcapIndex::build(&mut PcapReader::new(std::io::Cursor::new(data.clone())).unwrap()).unwrap();
+        let mut reader = IndexedReader::with_index(Seeks(std::io::Cursor::new(data), 0), index).unwrap();
+
+        assert_eq!(reader.range(2..8).count(), 6);
+        assert_eq!(reader.get(8).unwrap().unwrap().buf, vec![8; 4]);
+        assert_eq!(reader.into_inner().1, 1);
+    }
+}
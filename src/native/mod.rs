@@ -0,0 +1,400 @@
+//! Pure-Rust capture file implementation, usable without the C libpcap.
+pub mod pcap;
+pub mod pcapng;
+pub mod index;
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::time::now_duration;
+use crate::path::join_home;
+use crate::datalink::{DataLink, DEFAULT_LINKTYPE, DEFAULT_SNAPLEN};
+use crate::compress::Compression;
+use crate::stream::{InputStream, OutputStream, STDIO_PATH, open_input, create_output, input_stream, output_stream};
+use crate::packet::{FileFormat, LibPcapMode, LibPcapPacketInfo, Packet, TstampPrecision};
+use pcap::read_full;
+
+pub use pcap::{Endianness, PcapHeader, PcapReader, PcapWriter};
+pub use index::{IndexEntry, PcapIndex, IndexedReader, IndexedIterator};
+pub use pcapng::{
+    PcapngReader, PcapngWriter, PcapngBlock, PcapngPacket,
+    SectionHeader, InterfaceDescription, InterfaceStatistics, PacketOptions, NameRecord,
+};
+
+
+/// Reader for classic pcap or pcapng, the format is detected from the magic number
+pub enum CaptureReader<R> {
+    Pcap(PcapReader<R>),
+    Pcapng(PcapngReader<R>),
+}
+
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> PResult<Self> {
+        let mut magic = [0; 4];
+
+        if read_full(&mut reader, &mut magic)? != magic.len() {
+            return Err(LibPcapError::InvalidHeader { msg: "file header is too short".to_string() });
+        }
+
+        if u32::from_le_bytes(magic) == pcapng::PCAPNG_BLOCK_SHB {
+            Ok(Self::Pcapng(PcapngReader::with_magic(reader, magic)?))
+        }
+        else {
+            Ok(Self::Pcap(PcapReader::with_magic(reader, magic)?))
+        }
+    }
+
+    pub fn format(&self) -> FileFormat {
+        match self {
+            Self::Pcap(_) => FileFormat::Pcap,
+            Self::Pcapng(_) => FileFormat::Pcapng,
+        }
+    }
+
+    /// Link type of the file, for pcapng the one of the first interface
+    pub fn datalink(&self) -> DataLink {
+        match self {
+            Self::Pcap(v) => v.header().datalink(),
+            Self::Pcapng(v) => v.interfaces().first().map(|v| v.datalink).unwrap_or(DataLink::from(DEFAULT_LINKTYPE)),
+        }
+    }
+
+    /// Snapshot length of the file, for pcapng the one of the first interface
+    pub fn snaplen(&self) -> u32 {
+        match self {
+            Self::Pcap(v) => v.header().snaplen,
+            Self::Pcapng(v) => v.interfaces().first().map(|v| v.snaplen).unwrap_or(DEFAULT_SNAPLEN as u32),
+        }
+    }
+
+    /// Read the next packet, `Ok(None)` at the end of the stream
+    pub fn next_packet(&mut self) -> PResult<Option<Packet>> {
+        match self {
+            Self::Pcap(v) => v.next_packet(),
+            Self::Pcapng(v) => Ok(v.next_packet()?.map(|v| v.packet)),
+        }
+    }
+}
+
+
+/// Pure-Rust counterpart of `wrapper::LibPcap`, reads pcap and pcapng files and writes pcap files
+/// 
+/// Compressed input is detected from its magic bytes, output is compressed when the path ends
+/// with `.gz`, `.zst` or `.xz`, see the `gzip`, `zstd` and `xz` features.
+/// As in libpcap, the path `-` reads stdin or writes stdout.
+pub struct LibPcap {
+    reader: RefCell<Option<CaptureReader<InputStream>>>,
+    writer: RefCell<Option<PcapWriter<OutputStream>>>,
+    /// Size of the output before this handle wrote to it, unknown when appending to a compressed file
+    write_base: Option<u64>,
+    /// Uncompressed output file, to sync it to disk
+    sync_file: Option<File>,
+    precision: TstampPrecision,
+    linktype: i32,
+    snaplen: i32,
+}
+
+
+impl<'a> LibPcap {
+    pub fn open(path: &'a str, mode: &'a str) -> Result<Self, LibPcapError> {
+        Self::open_with_tstamp_precision(path, mode, TstampPrecision::Micro)
+    }
+
+    /// Open a pcap file with the given timestamp precision
+    /// 
+    /// Reading a nanosecond file with `TstampPrecision::Micro` truncates its timestamps,
+    /// writing with `TstampPrecision::Nano` produces a nanosecond pcap file.
+    /// 
+    pub fn open_with_tstamp_precision(path: &'a str, mode: &'a str, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, DEFAULT_LINKTYPE, DEFAULT_SNAPLEN, precision)
+    }
+
+    /// Open a pcap file for writing with the given link type and snaplen
+    pub fn open_with_linktype(path: &'a str, mode: &'a str, linktype: i32, snaplen: i32) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, linktype, snaplen, TstampPrecision::Micro)
+    }
+
+    /// Open a pcap file for writing with the given link type, snaplen and timestamp precision
+    pub fn open_with_header(path: &'a str, mode: &'a str, linktype: i32, snaplen: i32, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, linktype, snaplen, precision)
+    }
+
+    /// Open a pcap file for writing with the link type, snaplen and timestamp precision of another pcap file
+    pub fn open_like(path: &'a str, mode: &'a str, other: &LibPcap) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, other.linktype, other.snaplen, other.precision)
+    }
+
+    /// Read a pcap or pcapng capture from any reader, e.g. stdin, a pipe or an in-memory buffer
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::native::LibPcap;
+    /// use libpcap_rs::packet::TstampPrecision;
+    /// 
+    /// let buf = std::fs::read("tests/pcap/http_1.pcap").unwrap();
+    /// let mut f = LibPcap::from_reader(std::io::Cursor::new(buf), TstampPrecision::Micro).unwrap();
+    /// 
+    /// assert!(f.read().count() > 0);
+    /// ```
+    /// 
+    pub fn from_reader<R: Read + Send + 'static>(reader: R, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        Self::with_input(input_stream(reader)?, precision)
+    }
+
+    /// Write a pcap capture to any writer, e.g. stdout or a socket
+    pub fn from_writer<W: Write + Send + 'static>(writer: W, linktype: i32, snaplen: i32, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        let header = PcapHeader::new(DataLink::from(linktype), snaplen as u32, precision);
+        let writer = PcapWriter::new(output_stream(writer, Compression::None)?, header)?;
+
+        Ok(Self {
+            reader: RefCell::new(None),
+            writer: RefCell::new(Some(writer)),
+            write_base: Some(0),
+            sync_file: None,
+            precision,
+            linktype,
+            snaplen,
+        })
+    }
+
+    fn with_input(input: InputStream, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        let reader = CaptureReader::new(input)?;
+
+        Ok(Self {
+            linktype: reader.datalink().value(),
+            snaplen: reader.snaplen() as i32,
+            reader: RefCell::new(Some(reader)),
+            writer: RefCell::new(None),
+            write_base: None,
+            sync_file: None,
+            precision,
+        })
+    }
+
+    fn open_inner(path: &'a str, mode: &'a str, linktype: i32, snaplen: i32, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        let mode = LibPcapMode::from_str(mode).unwrap_or_default();
+        let invalid_file = |e| match e {
+            LibPcapError::InvalidHeader { .. } => LibPcapError::InvalidFile { path: path.to_string() },
+            e => e,
+        };
+
+        if mode == LibPcapMode::Read {
+            return Self::with_input(open_input(path)?, precision).map_err(invalid_file);
+        }
+
+        let append = mode == LibPcapMode::Append
+            && path != STDIO_PATH
+            && join_home(path).metadata().map(|v| v.len() > 0).unwrap_or_default();
+
+        let mut write_base = Some(0);
+
+        let (header, writer) = if append {
+            let input = open_input(path)?;
+
+            if input.compression() != Compression::None {
+                write_base = None;
+            }
+            else {
+                write_base = join_home(path).metadata().ok().map(|v| v.len());
+            }
+
+            let header = PcapReader::new(input).map_err(invalid_file)?.header().clone();
+
+            if header.datalink().value() != linktype || header.precision != precision {
+                return Err(LibPcapError::InvalidFile { path: path.to_string() });
+            }
+
+            let writer = PcapWriter::append(create_output(path, true)?, header.clone());
+
+            (header, writer)
+        }
+        else {
+            let header = PcapHeader::new(DataLink::from(linktype), snaplen as u32, precision);
+            let writer = PcapWriter::new(create_output(path, false)?, header.clone())?;
+
+            (header, writer)
+        };
+
+        let sync_file = if path != STDIO_PATH && writer.get_ref().compression() == Compression::None {
+            OpenOptions::new().append(true).open(join_home(path)).ok()
+        }
+        else {
+            None
+        };
+
+        Ok(Self {
+            reader: RefCell::new(None),
+            writer: RefCell::new(Some(writer)),
+            write_base,
+            sync_file,
+            precision,
+            linktype,
+            snaplen: header.snaplen as i32,
+        })
+    }
+
+    /// Timestamp precision of the opened file
+    pub fn tstamp_precision(&self) -> TstampPrecision {
+        self.precision
+    }
+
+    /// `DLT_*` link type of the opened file
+    pub fn linktype(&self) -> i32 {
+        self.linktype
+    }
+
+    /// Link type of the opened file
+    pub fn datalink(&self) -> DataLink {
+        DataLink::from(self.linktype)
+    }
+
+    /// Snapshot length of the opened file
+    pub fn snaplen(&self) -> i32 {
+        self.snaplen
+    }
+
+    /// Current size of the uncompressed output, `None` when not writing or appending to a compressed file
+    pub fn position(&self) -> Option<u64> {
+        let writer = self.writer.borrow();
+
+        Some(self.write_base? + writer.as_ref()?.bytes_written())
+    }
+
+    /// Iterate over the packets of an input file
+    /// 
+    /// Iterating yields owned `Packet` values, `LibPcapIterator::next_packet` lends them instead.
+    pub fn read(&mut self) -> LibPcapIterator<'_> {
+        LibPcapIterator { pcap: self, current: None, error: None }
+    }
+
+    /// Write the buffered packets to the output
+    pub fn flush(&self) -> PResult<()> {
+        if let Some(writer) = self.writer.borrow_mut().as_mut() {
+            writer.flush().map_err(|e| match e {
+                LibPcapError::IoError(e) => LibPcapError::FlushError { msg: e.to_string() },
+                e => e,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush the output and ask the OS to write it to disk
+    /// 
+    /// Outputs that are not files, e.g. compressed files, pipes or stdout, are only flushed.
+    pub fn sync(&self) -> PResult<()> {
+        self.flush()?;
+
+        if let Some(file) = &self.sync_file {
+            file.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush and close the output and the input, further writes fail with `OpenModeError`
+    /// 
+    /// Dropping a `LibPcap` closes it too but can't report errors, a writer should be closed
+    /// explicitly to know that every packet reached the file.
+    pub fn close(&mut self) -> PResult<()> {
+        self.reader.borrow_mut().take();
+        self.sync_file = None;
+
+        if let Some(mut writer) = self.writer.borrow_mut().take() {
+            writer.get_mut().try_finish().map_err(|e| match e {
+                LibPcapError::IoError(e) => LibPcapError::FlushError { msg: e.to_string() },
+                e => e,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Write to a pcap file with a custom timestamp
+    pub fn write_timestamp(&self, buf: &[u8], timestamp: i64) -> PResult<()> {
+        self.write_ts(buf, Duration::from_secs(timestamp as u64))
+    }
+
+    /// Write to a pcap file with a full precision timestamp
+    pub fn write_ts(&self, buf: &[u8], ts: Duration) -> PResult<()> {
+        self.write_ts_with_len(buf, ts, buf.len() as u32)
+    }
+
+    /// Write to a pcap file keeping the original wire length
+    pub fn write_ts_with_len(&self, buf: &[u8], ts: Duration, len: u32) -> PResult<()> {
+        let mut writer = self.writer.borrow_mut();
+        let writer = writer.as_mut().ok_or(LibPcapError::OpenModeError)?;
+
+        writer.write_packet(buf, ts, len).map_err(|e| match e {
+            LibPcapError::IoError(e) => LibPcapError::WriteError { msg: e.to_string() },
+            e => e,
+        })
+    }
+
+    /// Write a packet read from another pcap file or capture, keeping its timestamp and wire length
+    pub fn write_packet(&self, pkt: &LibPcapPacketInfo<'_>) -> PResult<()> {
+        self.write_ts_with_len(pkt.buf, pkt.ts, pkt.len)
+    }
+
+    /// Write to a pcap file
+    pub fn write(&self, buf: &[u8]) -> PResult<()> {
+        self.write_ts(buf, now_duration())
+    }
+}
+
+
+/// Iterator over the packets of a `native::LibPcap`
+pub struct LibPcapIterator<'a> {
+    pcap: &'a LibPcap,
+    current: Option<Packet>,
+    error: Option<LibPcapError>,
+}
+
+
+impl<'a> LibPcapIterator<'a> {
+    /// Read the next packet, borrowing it from the iterator
+    pub fn next_packet(&mut self) -> Option<LibPcapPacketInfo<'_>> {
+        self.current = self.read_packet();
+        self.current.as_ref().map(Packet::as_info)
+    }
+
+    /// Error that stopped the iteration, `None` if the file ended cleanly
+    pub fn last_error(&self) -> Option<&LibPcapError> {
+        self.error.as_ref()
+    }
+
+    fn read_packet(&mut self) -> Option<Packet> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let mut reader = self.pcap.reader.borrow_mut();
+
+        match reader.as_mut()?.next_packet() {
+            Ok(Some(mut pkt)) => {
+                pkt.ts = self.pcap.precision.truncate(pkt.ts);
+                Some(pkt)
+            },
+            Ok(None) => None,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
+        }
+    }
+}
+
+
+impl<'a> Iterator for LibPcapIterator<'a> {
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_packet()
+    }
+}
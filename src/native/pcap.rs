@@ -0,0 +1,415 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::datalink::DataLink;
+use crate::packet::{Packet, TstampPrecision};
+
+
+/// Magic number of a microsecond pcap file
+pub const PCAP_MAGIC_MICRO: u32 = 0xa1b2c3d4;
+/// Magic number of a nanosecond pcap file
+pub const PCAP_MAGIC_NANO: u32 = 0xa1b23c4d;
+pub const PCAP_VERSION_MAJOR: u16 = 2;
+pub const PCAP_VERSION_MINOR: u16 = 4;
+/// Size of the file header
+pub const PCAP_HEADER_LEN: usize = 24;
+/// Size of a packet record header
+pub const PCAP_RECORD_HEADER_LEN: usize = 16;
+/// Largest caplen accepted for a single record, guards against corrupted files
+pub const PCAP_MAX_CAPLEN: u32 = 256 * 1024 * 1024;
+
+
+/// Byte order of a capture file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+
+impl Endianness {
+    /// Byte order of the host
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") { Self::Big } else { Self::Little }
+    }
+
+    pub fn read_u16(&self, buf: &[u8]) -> u16 {
+        let value = [buf[0], buf[1]];
+
+        match self {
+            Self::Little => u16::from_le_bytes(value),
+            Self::Big => u16::from_be_bytes(value),
+        }
+    }
+
+    pub fn read_u32(&self, buf: &[u8]) -> u32 {
+        let value = [buf[0], buf[1], buf[2], buf[3]];
+
+        match self {
+            Self::Little => u32::from_le_bytes(value),
+            Self::Big => u32::from_be_bytes(value),
+        }
+    }
+
+    pub fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            Self::Little => value.to_le_bytes(),
+            Self::Big => value.to_be_bytes(),
+        }
+    }
+
+    pub fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            Self::Little => value.to_le_bytes(),
+            Self::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+
+/// Classic pcap file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapHeader {
+    pub endianness: Endianness,
+    pub precision: TstampPrecision,
+    pub version_major: u16,
+    pub version_minor: u16,
+    pub thiszone: i32,
+    pub sigfigs: u32,
+    pub snaplen: u32,
+    /// `LINKTYPE_*` value as stored in the file
+    pub linktype: u32,
+}
+
+
+impl PcapHeader {
+    /// Header in host byte order
+    pub fn new(datalink: DataLink, snaplen: u32, precision: TstampPrecision) -> Self {
+        Self {
+            endianness: Endianness::native(),
+            precision,
+            version_major: PCAP_VERSION_MAJOR,
+            version_minor: PCAP_VERSION_MINOR,
+            thiszone: 0,
+            sigfigs: 0,
+            snaplen,
+            linktype: datalink.linktype(),
+        }
+    }
+
+    /// Link type of the file, the FCS bits are ignored
+    pub fn datalink(&self) -> DataLink {
+        DataLink::from_linktype(self.linktype & 0x0fff_ffff)
+    }
+
+    /// Parse the 24 bytes file header
+    pub fn parse(buf: &[u8]) -> PResult<Self> {
+        if buf.len() < PCAP_HEADER_LEN {
+            return Err(LibPcapError::InvalidHeader { msg: "file header is too short".to_string() });
+        }
+
+        let (endianness, precision) = match Endianness::Little.read_u32(buf) {
+            PCAP_MAGIC_MICRO => (Endianness::Little, TstampPrecision::Micro),
+            PCAP_MAGIC_NANO => (Endianness::Little, TstampPrecision::Nano),
+            _ => match Endianness::Big.read_u32(buf) {
+                PCAP_MAGIC_MICRO => (Endianness::Big, TstampPrecision::Micro),
+                PCAP_MAGIC_NANO => (Endianness::Big, TstampPrecision::Nano),
+                magic => return Err(LibPcapError::InvalidHeader { msg: format!("unknown magic number 0x{magic:08x}") }),
+            },
+        };
+
+        Ok(Self {
+            endianness,
+            precision,
+            version_major: endianness.read_u16(&buf[4..]),
+            version_minor: endianness.read_u16(&buf[6..]),
+            thiszone: endianness.read_u32(&buf[8..]) as i32,
+            sigfigs: endianness.read_u32(&buf[12..]),
+            snaplen: endianness.read_u32(&buf[16..]),
+            linktype: endianness.read_u32(&buf[20..]),
+        })
+    }
+
+    /// Encode the 24 bytes file header
+    pub fn to_bytes(&self) -> [u8; PCAP_HEADER_LEN] {
+        let mut buf = [0; PCAP_HEADER_LEN];
+        let e = self.endianness;
+        let magic = match self.precision {
+            TstampPrecision::Micro => PCAP_MAGIC_MICRO,
+            TstampPrecision::Nano => PCAP_MAGIC_NANO,
+        };
+
+        buf[0..4].copy_from_slice(&e.u32_bytes(magic));
+        buf[4..6].copy_from_slice(&e.u16_bytes(self.version_major));
+        buf[6..8].copy_from_slice(&e.u16_bytes(self.version_minor));
+        buf[8..12].copy_from_slice(&e.u32_bytes(self.thiszone as u32));
+        buf[12..16].copy_from_slice(&e.u32_bytes(self.sigfigs));
+        buf[16..20].copy_from_slice(&e.u32_bytes(self.snaplen));
+        buf[20..24].copy_from_slice(&e.u32_bytes(self.linktype));
+
+        buf
+    }
+}
+
+
+/// Read until `buf` is full or the stream ends, returns the number of bytes read
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut size = 0;
+
+    while size < buf.len() {
+        match reader.read(&mut buf[size..]) {
+            Ok(0) => break,
+            Ok(n) => size += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(size)
+}
+
+
+/// Classic pcap reader over any byte stream
+/// 
+/// # Example:
+/// 
+/// ```rust
+/// use libpcap_rs::native::PcapReader;
+/// 
+/// let file = std::fs::File::open("tests/pcap/http_1.pcap").unwrap();
+/// let mut reader = PcapReader::new(std::io::BufReader::new(file)).unwrap();
+/// 
+/// while let Ok(Some(pkt)) = reader.next_packet() {
+///     println!("{:?} {}", pkt.ts, pkt.caplen);
+/// }
+/// ```
+/// 
+pub struct PcapReader<R> {
+    reader: R,
+    header: PcapHeader,
+}
+
+
+impl<R: Read> PcapReader<R> {
+    /// Read the file header
+    pub fn new(mut reader: R) -> PResult<Self> {
+        let mut magic = [0; 4];
+
+        if read_full(&mut reader, &mut magic)? != magic.len() {
+            return Err(LibPcapError::InvalidHeader { msg: "file header is too short".to_string() });
+        }
+
+        Self::with_magic(reader, magic)
+    }
+
+    /// Read the rest of the file header when the magic number was already consumed
+    pub(crate) fn with_magic(mut reader: R, magic: [u8; 4]) -> PResult<Self> {
+        let mut buf = [0; PCAP_HEADER_LEN];
+
+        buf[..4].copy_from_slice(&magic);
+
+        if read_full(&mut reader, &mut buf[4..])? != PCAP_HEADER_LEN - 4 {
+            return Err(LibPcapError::InvalidHeader { msg: "file header is too short".to_string() });
+        }
+
+        let header = PcapHeader::parse(&buf)?;
+
+        Ok(Self {
+            reader,
+            header,
+        })
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        &self.header
+    }
+
+    /// Read the next packet record, `Ok(None)` at the end of the stream
+    pub fn next_packet(&mut self) -> PResult<Option<Packet>> {
+        let mut record = [0; PCAP_RECORD_HEADER_LEN];
+
+        match read_full(&mut self.reader, &mut record)? {
+            0 => return Ok(None),
+            PCAP_RECORD_HEADER_LEN => {},
+            n => return Err(LibPcapError::TruncatedRecord {
+                msg: format!("tried to read {PCAP_RECORD_HEADER_LEN} header bytes, only got {n}"),
+            }),
+        }
+
+        let e = self.header.endianness;
+        let sec = e.read_u32(&record[0..]);
+        let frac = e.read_u32(&record[4..]);
+        let caplen = e.read_u32(&record[8..]);
+        let len = e.read_u32(&record[12..]);
+
+        if caplen > PCAP_MAX_CAPLEN {
+            return Err(LibPcapError::InvalidRecord { msg: format!("caplen {caplen} is too large") });
+        }
+
+        let mut buf = vec![0; caplen as usize];
+
+        let n = read_full(&mut self.reader, &mut buf)?;
+
+        if n != buf.len() {
+            return Err(LibPcapError::TruncatedRecord {
+                msg: format!("tried to read {caplen} captured bytes, only got {n}"),
+            });
+        }
+
+        let ts = self.header.precision.to_duration(sec as u64, frac);
+
+        Ok(Some(Packet::new(buf, ts, len)))
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+
+/// Classic pcap writer over any byte stream
+/// 
+/// # Example:
+/// 
+/// ```rust
+/// use libpcap_rs::DataLink;
+/// use libpcap_rs::native::{PcapHeader, PcapWriter};
+/// use libpcap_rs::packet::TstampPrecision;
+/// 
+/// let header = PcapHeader::new(DataLink::En10mb, 65535, TstampPrecision::Nano);
+/// let mut writer = PcapWriter::new(Vec::new(), header).unwrap();
+/// 
+/// writer.write_packet(b"\x00\x0c\x29\xaf\x7f\xfe", std::time::Duration::new(1, 5), 60).unwrap();
+/// assert_eq!(writer.get_ref().len(), 24 + 16 + 6);
+/// ```
+/// 
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    header: PcapHeader,
+    written: u64,
+}
+
+
+impl<W: Write> PcapWriter<W> {
+    /// Write the file header
+    pub fn new(mut writer: W, header: PcapHeader) -> PResult<Self> {
+        writer.write_all(&header.to_bytes())?;
+
+        Ok(Self {
+            writer,
+            header,
+            written: PCAP_HEADER_LEN as u64,
+        })
+    }
+
+    /// Continue a stream that already holds `header`, nothing is written
+    pub fn append(writer: W, header: PcapHeader) -> Self {
+        Self {
+            writer,
+            header,
+            written: 0,
+        }
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        &self.header
+    }
+
+    /// Number of bytes written by this writer, the file header included
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    /// Write a packet record
+    /// 
+    /// # Args:
+    /// 
+    /// - `buf`: Captured bytes
+    /// - `ts`: Duration since the UNIX epoch, truncated to the file timestamp precision
+    /// - `len`: Original length of the packet, raised to `buf.len()` when smaller
+    /// 
+    pub fn write_packet(&mut self, buf: &[u8], ts: Duration, len: u32) -> PResult<()> {
+        let e = self.header.endianness;
+        let caplen = buf.len() as u32;
+        let mut record = [0; PCAP_RECORD_HEADER_LEN];
+
+        record[0..4].copy_from_slice(&e.u32_bytes(ts.as_secs() as u32));
+        record[4..8].copy_from_slice(&e.u32_bytes(self.header.precision.subsec(ts)));
+        record[8..12].copy_from_slice(&e.u32_bytes(caplen));
+        record[12..16].copy_from_slice(&e.u32_bytes(len.max(caplen)));
+
+        self.writer.write_all(&record)?;
+        self.writer.write_all(buf)?;
+        self.written += (PCAP_RECORD_HEADER_LEN + buf.len()) as u64;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> PResult<()> {
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcap_big_endian_nano() {
+        let mut header = PcapHeader::new(DataLink::Raw, 262144, TstampPrecision::Nano);
+        header.endianness = Endianness::Big;
+
+        let mut writer = PcapWriter::new(Vec::new(), header.clone()).unwrap();
+        writer.write_packet(b"\x45\x00\x00\x14", Duration::new(1700000000, 123456789), 40).unwrap();
+        let buf = writer.into_inner();
+
+        assert_eq!(&buf[..4], b"\xa1\xb2\x3c\x4d");
+        assert_eq!(&buf[20..24], b"\x00\x00\x00\x65");
+
+        let mut reader = PcapReader::new(&buf[..]).unwrap();
+        assert_eq!(reader.header(), &header);
+        assert_eq!(reader.header().datalink(), DataLink::Raw);
+
+        let pkt = reader.next_packet().unwrap().unwrap();
+        assert_eq!(pkt.ts, Duration::new(1700000000, 123456789));
+        assert_eq!(pkt.caplen, 4);
+        assert_eq!(pkt.len, 40);
+        assert_eq!(pkt.buf, b"\x45\x00\x00\x14");
+        assert_eq!(reader.next_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn test_pcap_truncated_record() {
+        let header = PcapHeader::new(DataLink::En10mb, 65535, TstampPrecision::Micro);
+        let mut writer = PcapWriter::new(Vec::new(), header).unwrap();
+        writer.write_packet(b"\x00\x0c\x29\xaf\x7f\xfe", Duration::from_secs(1), 6).unwrap();
+        let buf = writer.into_inner();
+
+        let mut reader = PcapReader::new(&buf[..buf.len() - 2]).unwrap();
+        assert!(matches!(reader.next_packet(), Err(LibPcapError::TruncatedRecord { .. })));
+    }
+}
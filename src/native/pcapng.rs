@@ -0,0 +1,1199 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::datalink::DataLink;
+use crate::packet::{LibPcapMode, LibPcapPacketInfo, Packet, TstampPrecision};
+use crate::path::join_home;
+use crate::stream::{InputStream, OutputStream, STDIO_PATH, open_input, create_output};
+use super::pcap::{Endianness, PCAP_MAX_CAPLEN, read_full};
+
+
+/// Section Header Block type
+pub const PCAPNG_BLOCK_SHB: u32 = 0x0a0d0d0a;
+/// Interface Description Block type
+pub const PCAPNG_BLOCK_IDB: u32 = 0x00000001;
+/// Obsolete Packet Block type
+pub const PCAPNG_BLOCK_OPB: u32 = 0x00000002;
+/// Simple Packet Block type
+pub const PCAPNG_BLOCK_SPB: u32 = 0x00000003;
+/// Name Resolution Block type
+pub const PCAPNG_BLOCK_NRB: u32 = 0x00000004;
+/// Interface Statistics Block type
+pub const PCAPNG_BLOCK_ISB: u32 = 0x00000005;
+/// Enhanced Packet Block type
+pub const PCAPNG_BLOCK_EPB: u32 = 0x00000006;
+/// Custom Block types, copiable and non-copiable
+pub const PCAPNG_BLOCK_CUSTOM: u32 = 0x00000bad;
+pub const PCAPNG_BLOCK_CUSTOM_NOCOPY: u32 = 0x40000bad;
+/// Byte order magic of the Section Header Block
+pub const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_HARDWARE: u16 = 2;
+const SHB_OS: u16 = 3;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_DESCRIPTION: u16 = 3;
+const IF_TSRESOL: u16 = 9;
+const IF_FILTER: u16 = 11;
+const EPB_FLAGS: u16 = 2;
+const EPB_HASH: u16 = 3;
+const EPB_DROPCOUNT: u16 = 4;
+const ISB_IFRECV: u16 = 4;
+const ISB_IFDROP: u16 = 5;
+const NRB_RECORD_END: u16 = 0;
+const NRB_RECORD_IPV4: u16 = 1;
+const NRB_RECORD_IPV6: u16 = 2;
+
+
+/// Section Header Block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionHeader {
+    pub endianness: Endianness,
+    pub version_major: u16,
+    pub version_minor: u16,
+    pub hardware: Option<String>,
+    pub os: Option<String>,
+    pub userappl: Option<String>,
+    pub comment: Option<String>,
+}
+
+
+impl Default for SectionHeader {
+    fn default() -> Self {
+        Self {
+            endianness: Endianness::native(),
+            version_major: 1,
+            version_minor: 0,
+            hardware: None,
+            os: None,
+            userappl: None,
+            comment: None,
+        }
+    }
+}
+
+
+/// Interface Description Block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceDescription {
+    pub datalink: DataLink,
+    pub snaplen: u32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// `if_tsresol`, power of 10 (or of 2 when the high bit is set) of the timestamp unit
+    pub tsresol: u8,
+    /// `if_filter` as a libpcap filter string
+    pub filter: Option<String>,
+    pub comment: Option<String>,
+}
+
+
+impl InterfaceDescription {
+    pub fn new(datalink: DataLink, snaplen: u32) -> Self {
+        Self {
+            datalink,
+            snaplen,
+            name: None,
+            description: None,
+            tsresol: 6,
+            filter: None,
+            comment: None,
+        }
+    }
+
+    /// Set `if_tsresol` from a timestamp precision
+    pub fn with_tstamp_precision(mut self, precision: TstampPrecision) -> Self {
+        self.tsresol = match precision {
+            TstampPrecision::Micro => 6,
+            TstampPrecision::Nano => 9,
+        };
+        self
+    }
+
+    /// Convert a duration since the UNIX epoch to timestamp units of this interface
+    /// 
+    /// Fails with `InvalidRecord` when the timestamp doesn't fit in 64 bits at this resolution.
+    pub fn ts_to_units(&self, ts: Duration) -> PResult<u64> {
+        let units = if self.tsresol & 0x80 != 0 {
+            1u128.checked_shl((self.tsresol & 0x7f) as u32)
+                .and_then(|v| ts.as_nanos().checked_mul(v))
+                .map(|v| v / 1_000_000_000)
+        }
+        else {
+            match self.tsresol as u32 {
+                exp @ 0..=9 => Some(ts.as_secs() as u128 * 10u128.pow(exp) + (ts.subsec_nanos() / 10u32.pow(9 - exp)) as u128),
+                exp => 10u128.checked_pow(exp - 9).and_then(|v| ts.as_nanos().checked_mul(v)),
+            }
+        };
+
+        units.and_then(|v| u64::try_from(v).ok()).ok_or_else(|| LibPcapError::InvalidRecord {
+            msg: format!("timestamp {ts:?} overflows if_tsresol {}", self.tsresol),
+        })
+    }
+
+    /// Convert timestamp units of this interface to a duration since the UNIX epoch
+    pub fn units_to_ts(&self, units: u64) -> Duration {
+        if self.tsresol & 0x80 != 0 {
+            let shift = (self.tsresol & 0x7f) as u32;
+            let nanos = ((units as u128) * 1_000_000_000) >> shift;
+            return Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32);
+        }
+
+        match self.tsresol as u32 {
+            exp @ 0..=9 => {
+                let div = 10u64.pow(exp);
+                Duration::new(units / div, ((units % div) * 10u64.pow(9 - exp)) as u32)
+            },
+            exp => {
+                let nanos = units as u128 / 10u128.pow(exp.min(38) - 9);
+                Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+            },
+        }
+    }
+}
+
+
+/// Enhanced Packet Block options
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PacketOptions {
+    pub comment: Option<String>,
+    /// `epb_flags`, direction, reception type, FCS length and link-layer errors
+    pub flags: Option<u32>,
+    /// `epb_hash`, algorithm byte followed by the hash value
+    pub hash: Option<Vec<u8>>,
+    /// `epb_dropcount`, packets lost between this packet and the previous one
+    pub dropcount: Option<u64>,
+}
+
+
+/// Name Resolution Block record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRecord {
+    pub addr: IpAddr,
+    pub names: Vec<String>,
+}
+
+
+/// Encodes blocks and options in the section byte order
+struct BlockBuf {
+    endianness: Endianness,
+    buf: Vec<u8>,
+}
+
+
+impl BlockBuf {
+    fn new(endianness: Endianness) -> Self {
+        Self { endianness, buf: vec![] }
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.buf.extend_from_slice(&self.endianness.u16_bytes(value));
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&self.endianness.u32_bytes(value));
+        self
+    }
+
+    fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(value);
+        self.buf.resize((self.buf.len() + 3) & !3, 0);
+        self
+    }
+
+    /// Option value lengths are 16 bits, a longer value fails with `InvalidRecord`
+    fn option(&mut self, code: u16, value: &[u8]) -> PResult<&mut Self> {
+        let len = u16::try_from(value.len()).map_err(|_| LibPcapError::InvalidRecord {
+            msg: format!("option {code} is {} bytes long, at most {} fit", value.len(), u16::MAX),
+        })?;
+
+        Ok(self.u16(code).u16(len).bytes(value))
+    }
+
+    fn option_str(&mut self, code: u16, value: &Option<String>) -> PResult<&mut Self> {
+        if let Some(value) = value {
+            self.option(code, value.as_bytes())?;
+        }
+        Ok(self)
+    }
+
+    fn end_of_options(&mut self, start: usize) -> &mut Self {
+        if self.buf.len() > start {
+            self.u16(OPT_ENDOFOPT).u16(0);
+        }
+        self
+    }
+
+    /// Wrap the body into a block with its type and total length
+    fn block(&self, block_type: u32) -> Vec<u8> {
+        let total_length = (self.buf.len() + 12) as u32;
+        let mut block = BlockBuf::new(self.endianness);
+
+        block.u32(block_type).u32(total_length);
+        block.buf.extend_from_slice(&self.buf);
+        block.u32(total_length);
+
+        block.buf
+    }
+}
+
+
+/// pcapng writer over any byte stream
+/// 
+/// # Example:
+/// 
+/// ```rust
+/// use libpcap_rs::DataLink;
+/// use libpcap_rs::native::{PcapngWriter, SectionHeader, InterfaceDescription, PacketOptions};
+/// 
+/// let mut writer = PcapngWriter::new(Vec::new(), SectionHeader::default()).unwrap();
+/// let mut iface = InterfaceDescription::new(DataLink::En10mb, 65535);
+/// iface.name = Some("eth0".to_string());
+/// 
+/// let id = writer.add_interface(iface).unwrap();
+/// let options = PacketOptions { comment: Some("first packet".to_string()), ..Default::default() };
+/// writer.write_packet_with_options(id, b"\x00\x0c\x29\xaf\x7f\xfe", std::time::Duration::new(1, 0), 6, &options).unwrap();
+/// ```
+/// 
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    section: SectionHeader,
+    interfaces: Vec<InterfaceDescription>,
+}
+
+
+impl<W: Write> PcapngWriter<W> {
+    /// Write the Section Header Block
+    pub fn new(mut writer: W, section: SectionHeader) -> PResult<Self> {
+        let mut body = BlockBuf::new(section.endianness);
+
+        body.u32(PCAPNG_BYTE_ORDER_MAGIC)
+            .u16(section.version_major)
+            .u16(section.version_minor)
+            .u32(u32::MAX)
+            .u32(u32::MAX);
+
+        let start = body.buf.len();
+
+        body.option_str(OPT_COMMENT, &section.comment)?
+            .option_str(SHB_HARDWARE, &section.hardware)?
+            .option_str(SHB_OS, &section.os)?
+            .option_str(SHB_USERAPPL, &section.userappl)?
+            .end_of_options(start);
+
+        writer.write_all(&body.block(PCAPNG_BLOCK_SHB))?;
+
+        Ok(Self {
+            writer,
+            section,
+            interfaces: vec![],
+        })
+    }
+
+    pub fn section(&self) -> &SectionHeader {
+        &self.section
+    }
+
+    pub fn interfaces(&self) -> &[InterfaceDescription] {
+        &self.interfaces
+    }
+
+    /// Write an Interface Description Block, returns the interface id used by packets
+    pub fn add_interface(&mut self, iface: InterfaceDescription) -> PResult<u32> {
+        let mut body = BlockBuf::new(self.section.endianness);
+
+        body.u16(iface.datalink.linktype() as u16)
+            .u16(0)
+            .u32(iface.snaplen);
+
+        let start = body.buf.len();
+
+        body.option_str(OPT_COMMENT, &iface.comment)?
+            .option_str(IF_NAME, &iface.name)?
+            .option_str(IF_DESCRIPTION, &iface.description)?;
+
+        if iface.tsresol != 6 {
+            body.option(IF_TSRESOL, &[iface.tsresol])?;
+        }
+
+        if let Some(filter) = &iface.filter {
+            let mut value = vec![0];
+            value.extend_from_slice(filter.as_bytes());
+            body.option(IF_FILTER, &value)?;
+        }
+
+        body.end_of_options(start);
+
+        self.writer.write_all(&body.block(PCAPNG_BLOCK_IDB))?;
+        self.interfaces.push(iface);
+
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    /// Write an Enhanced Packet Block
+    pub fn write_packet(&mut self, interface_id: u32, buf: &[u8], ts: Duration, len: u32) -> PResult<()> {
+        self.write_packet_with_options(interface_id, buf, ts, len, &PacketOptions::default())
+    }
+
+    /// Write an Enhanced Packet Block with a comment, flags, hash or drop count
+    /// 
+    /// # Args:
+    /// 
+    /// - `interface_id`: id returned by `add_interface`
+    /// - `buf`: Captured bytes
+    /// - `ts`: Duration since the UNIX epoch
+    /// - `len`: Original length of the packet, raised to `buf.len()` when smaller
+    /// - `options`: Packet options
+    /// 
+    pub fn write_packet_with_options(&mut self, interface_id: u32, buf: &[u8], ts: Duration, len: u32, options: &PacketOptions) -> PResult<()> {
+        let iface = self.interfaces.get(interface_id as usize).ok_or(LibPcapError::InvalidRecord {
+            msg: format!("unknown interface id {interface_id}"),
+        })?;
+
+        let units = iface.ts_to_units(ts)?;
+        let caplen = buf.len() as u32;
+        let mut body = BlockBuf::new(self.section.endianness);
+
+        body.u32(interface_id)
+            .u32((units >> 32) as u32)
+            .u32(units as u32)
+            .u32(caplen)
+            .u32(len.max(caplen))
+            .bytes(buf);
+
+        let start = body.buf.len();
+
+        body.option_str(OPT_COMMENT, &options.comment)?;
+
+        if let Some(flags) = options.flags {
+            body.option(EPB_FLAGS, &self.section.endianness.u32_bytes(flags))?;
+        }
+
+        if let Some(hash) = &options.hash {
+            body.option(EPB_HASH, hash)?;
+        }
+
+        if let Some(dropcount) = options.dropcount {
+            let value = match self.section.endianness {
+                Endianness::Little => dropcount.to_le_bytes(),
+                Endianness::Big => dropcount.to_be_bytes(),
+            };
+            body.option(EPB_DROPCOUNT, &value)?;
+        }
+
+        body.end_of_options(start);
+
+        self.writer.write_all(&body.block(PCAPNG_BLOCK_EPB))?;
+
+        Ok(())
+    }
+
+    /// Write a Name Resolution Block
+    pub fn write_name_resolution(&mut self, records: &[NameRecord]) -> PResult<()> {
+        let mut body = BlockBuf::new(self.section.endianness);
+
+        for record in records {
+            let mut value = match record.addr {
+                IpAddr::V4(addr) => addr.octets().to_vec(),
+                IpAddr::V6(addr) => addr.octets().to_vec(),
+            };
+
+            for name in &record.names {
+                value.extend_from_slice(name.as_bytes());
+                value.push(0);
+            }
+
+            let code = if record.addr.is_ipv4() { NRB_RECORD_IPV4 } else { NRB_RECORD_IPV6 };
+            body.option(code, &value)?;
+        }
+
+        body.u16(NRB_RECORD_END).u16(0);
+
+        self.writer.write_all(&body.block(PCAPNG_BLOCK_NRB))?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> PResult<()> {
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+
+impl PcapngWriter<OutputStream> {
+    /// Create a pcapng file, in append mode a new section is added at the end of the file
+    /// 
+    /// Appending to a file that isn't pcapng fails with `InvalidFile`.
+    /// 
+    /// The output is compressed when the path ends with `.gz`, `.zst` or `.xz`,
+    /// appending to a compressed file adds a new compressed stream. `-` writes stdout.
+    /// 
+    /// # Args:
+    /// 
+    /// - `path`: pcapng file path
+    /// - `mode`: `w` or `a`
+    /// - `section`: Section Header Block options
+    /// 
+    pub fn open(path: &str, mode: &str, section: SectionHeader) -> PResult<Self> {
+        let append = LibPcapMode::from_str(mode).unwrap_or_default() == LibPcapMode::Append;
+
+        // A section appended to a classic pcap file would corrupt it
+        if append && path != STDIO_PATH && join_home(path).metadata().map(|v| v.len() > 0).unwrap_or_default() {
+            let mut magic = [0; 4];
+
+            if read_full(&mut open_input(path)?, &mut magic)? != magic.len() || u32::from_le_bytes(magic) != PCAPNG_BLOCK_SHB {
+                return Err(LibPcapError::InvalidFile { path: path.to_string() });
+            }
+        }
+
+        Self::new(create_output(path, append)?, section)
+    }
+
+    /// Flush the output and write the trailer of a compressed file, nothing can be written afterwards
+    /// 
+    /// Dropping the writer does it too but can't report errors.
+    pub fn close(&mut self) -> PResult<()> {
+        self.writer.try_finish().map_err(|e| match e {
+            LibPcapError::IoError(e) => LibPcapError::FlushError { msg: e.to_string() },
+            e => e,
+        })
+    }
+}
+
+
+/// Interface Statistics Block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceStatistics {
+    pub interface_id: u32,
+    pub ts: Duration,
+    /// `isb_ifrecv`, packets received by the interface
+    pub ifrecv: Option<u64>,
+    /// `isb_ifdrop`, packets dropped by the interface
+    pub ifdrop: Option<u64>,
+    pub comment: Option<String>,
+}
+
+
+/// Packet read from a pcapng file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapngPacket {
+    /// Index of the interface in the current section
+    pub interface_id: u32,
+    pub packet: Packet,
+    pub options: PacketOptions,
+}
+
+
+impl PcapngPacket {
+    /// Borrow the packet as a `LibPcapPacketInfo`
+    pub fn as_info(&self) -> LibPcapPacketInfo<'_> {
+        self.packet.as_info()
+    }
+}
+
+
+/// pcapng block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PcapngBlock {
+    Section(SectionHeader),
+    Interface(InterfaceDescription),
+    /// Enhanced, Simple or obsolete Packet Block
+    Packet(PcapngPacket),
+    NameResolution(Vec<NameRecord>),
+    InterfaceStatistics(InterfaceStatistics),
+    Custom {
+        /// Private Enterprise Number
+        pen: u32,
+        data: Vec<u8>,
+        copiable: bool,
+    },
+    /// Block type this reader doesn't know, kept as is
+    Unknown {
+        block_type: u32,
+        body: Vec<u8>,
+    },
+}
+
+
+/// Iterates over `(code, value)` options until `opt_endofopt`
+struct OptionIter<'a> {
+    endianness: Endianness,
+    buf: &'a [u8],
+}
+
+
+impl<'a> Iterator for OptionIter<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.len() < 4 {
+            return None;
+        }
+
+        let code = self.endianness.read_u16(self.buf);
+        let length = self.endianness.read_u16(&self.buf[2..]) as usize;
+
+        if code == OPT_ENDOFOPT || self.buf.len() < 4 + length {
+            return None;
+        }
+
+        let value = &self.buf[4..4 + length];
+        self.buf = self.buf.get(4 + ((length + 3) & !3)..).unwrap_or_default();
+
+        Some((code, value))
+    }
+}
+
+
+fn options(endianness: Endianness, buf: &[u8]) -> OptionIter<'_> {
+    OptionIter { endianness, buf }
+}
+
+
+fn option_string(value: &[u8]) -> Option<String> {
+    Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_string())
+}
+
+
+fn option_u64(endianness: Endianness, value: &[u8]) -> Option<u64> {
+    let value: [u8; 8] = value.get(..8)?.try_into().ok()?;
+
+    match endianness {
+        Endianness::Little => Some(u64::from_le_bytes(value)),
+        Endianness::Big => Some(u64::from_be_bytes(value)),
+    }
+}
+
+
+fn invalid_block(block_type: u32) -> LibPcapError {
+    LibPcapError::InvalidRecord { msg: format!("block 0x{block_type:08x} is too short") }
+}
+
+
+/// pcapng reader over any byte stream
+/// 
+/// Interface Description Blocks are tracked per section, so packets can be matched with the interface
+/// they were captured on, unknown blocks are returned as `PcapngBlock::Unknown`.
+/// 
+/// # Example:
+/// 
+/// ```rust
+/// use libpcap_rs::DataLink;
+/// use libpcap_rs::native::{PcapngReader, PcapngWriter, SectionHeader, InterfaceDescription};
+/// 
+/// let mut writer = PcapngWriter::new(Vec::new(), SectionHeader::default()).unwrap();
+/// writer.add_interface(InterfaceDescription::new(DataLink::En10mb, 65535)).unwrap();
+/// writer.write_packet(0, b"\x00\x0c\x29\xaf\x7f\xfe", std::time::Duration::new(1, 0), 6).unwrap();
+/// let buf = writer.into_inner();
+/// 
+/// let mut reader = PcapngReader::new(&buf[..]).unwrap();
+/// 
+/// while let Ok(Some(pkt)) = reader.next_packet() {
+///     let iface = reader.interface(pkt.interface_id).unwrap();
+///     println!("{:?} {:?} {:?}", iface.datalink, pkt.packet.ts, pkt.options.comment);
+/// }
+/// ```
+/// 
+pub struct PcapngReader<R> {
+    reader: R,
+    section: SectionHeader,
+    interfaces: Vec<InterfaceDescription>,
+    pending: VecDeque<PcapngBlock>,
+    done: bool,
+}
+
+
+impl<R: Read> PcapngReader<R> {
+    /// Read the Section Header Block and the blocks up to the first interface
+    pub fn new(mut reader: R) -> PResult<Self> {
+        let mut magic = [0; 4];
+
+        if read_full(&mut reader, &mut magic)? != magic.len() {
+            return Err(LibPcapError::InvalidHeader { msg: "file header is too short".to_string() });
+        }
+
+        Self::with_magic(reader, magic)
+    }
+
+    /// Same as `new` when the block type of the first block was already consumed
+    pub(crate) fn with_magic(reader: R, magic: [u8; 4]) -> PResult<Self> {
+        if u32::from_le_bytes(magic) != PCAPNG_BLOCK_SHB {
+            return Err(LibPcapError::InvalidHeader { msg: "missing section header block".to_string() });
+        }
+
+        let mut pcapng = Self {
+            reader,
+            section: SectionHeader::default(),
+            interfaces: vec![],
+            pending: VecDeque::new(),
+            done: false,
+        };
+
+        pcapng.read_section()?;
+
+        // Like libpcap, make the first interface known before the first packet is requested.
+        while let Some(block) = pcapng.read_block()? {
+            let stop = matches!(block, PcapngBlock::Interface(_) | PcapngBlock::Packet(_));
+
+            pcapng.pending.push_back(block);
+
+            if stop {
+                break;
+            }
+        }
+
+        Ok(pcapng)
+    }
+
+    /// Current section
+    pub fn section(&self) -> &SectionHeader {
+        &self.section
+    }
+
+    /// Interfaces of the current section
+    pub fn interfaces(&self) -> &[InterfaceDescription] {
+        &self.interfaces
+    }
+
+    pub fn interface(&self, interface_id: u32) -> Option<&InterfaceDescription> {
+        self.interfaces.get(interface_id as usize)
+    }
+
+    /// Read the next block, `Ok(None)` at the end of the stream
+    pub fn next_block(&mut self) -> PResult<Option<PcapngBlock>> {
+        if let Some(block) = self.pending.pop_front() {
+            return Ok(Some(block));
+        }
+
+        self.read_block()
+    }
+
+    /// Read the next packet, other blocks only update the section and interface metadata
+    pub fn next_packet(&mut self) -> PResult<Option<PcapngPacket>> {
+        while let Some(block) = self.next_block()? {
+            if let PcapngBlock::Packet(pkt) = block {
+                return Ok(Some(pkt));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> PResult<()> {
+        let n = read_full(&mut self.reader, buf)?;
+
+        if n != buf.len() {
+            return Err(LibPcapError::TruncatedRecord {
+                msg: format!("tried to read {} block bytes, only got {n}", buf.len()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Read a Section Header Block after its block type
+    fn read_section(&mut self) -> PResult<PcapngBlock> {
+        let mut head = [0; 8];
+        self.read_exact(&mut head)?;
+
+        let endianness = match Endianness::Little.read_u32(&head[4..]) {
+            PCAPNG_BYTE_ORDER_MAGIC => Endianness::Little,
+            _ if Endianness::Big.read_u32(&head[4..]) == PCAPNG_BYTE_ORDER_MAGIC => Endianness::Big,
+            magic => return Err(LibPcapError::InvalidHeader { msg: format!("unknown byte order magic 0x{magic:08x}") }),
+        };
+
+        let total_length = endianness.read_u32(&head) as usize;
+
+        if total_length < 28 || total_length % 4 != 0 || total_length > PCAP_MAX_CAPLEN as usize {
+            return Err(LibPcapError::InvalidHeader { msg: format!("invalid section length {total_length}") });
+        }
+
+        let mut body = vec![0; total_length - 12];
+        self.read_exact(&mut body)?;
+        body.truncate(total_length - 16);
+
+        let mut section = SectionHeader {
+            endianness,
+            version_major: endianness.read_u16(&body),
+            version_minor: endianness.read_u16(&body[2..]),
+            ..Default::default()
+        };
+
+        for (code, value) in options(endianness, &body[12..]) {
+            match code {
+                OPT_COMMENT => section.comment = option_string(value),
+                SHB_HARDWARE => section.hardware = option_string(value),
+                SHB_OS => section.os = option_string(value),
+                SHB_USERAPPL => section.userappl = option_string(value),
+                _ => {},
+            }
+        }
+
+        self.section = section.clone();
+        self.interfaces.clear();
+
+        Ok(PcapngBlock::Section(section))
+    }
+
+    fn read_block(&mut self) -> PResult<Option<PcapngBlock>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let mut head = [0; 4];
+
+        match read_full(&mut self.reader, &mut head)? {
+            0 => {
+                self.done = true;
+                return Ok(None);
+            },
+            4 => {},
+            n => return Err(LibPcapError::TruncatedRecord {
+                msg: format!("tried to read 4 block type bytes, only got {n}"),
+            }),
+        }
+
+        let e = self.section.endianness;
+        let block_type = e.read_u32(&head);
+
+        if block_type == PCAPNG_BLOCK_SHB {
+            return self.read_section().map(Some);
+        }
+
+        self.read_exact(&mut head)?;
+
+        let total_length = e.read_u32(&head) as usize;
+
+        if total_length < 12 || total_length % 4 != 0 || total_length > PCAP_MAX_CAPLEN as usize {
+            return Err(LibPcapError::InvalidRecord { msg: format!("invalid block length {total_length}") });
+        }
+
+        let mut body = vec![0; total_length - 8];
+        self.read_exact(&mut body)?;
+
+        if e.read_u32(&body[total_length - 12..]) as usize != total_length {
+            return Err(LibPcapError::InvalidRecord { msg: format!("block 0x{block_type:08x} length mismatch") });
+        }
+
+        body.truncate(total_length - 12);
+
+        let block = match block_type {
+            PCAPNG_BLOCK_IDB => {
+                let iface = self.parse_interface(&body)?;
+                self.interfaces.push(iface.clone());
+                PcapngBlock::Interface(iface)
+            },
+            PCAPNG_BLOCK_EPB => PcapngBlock::Packet(self.parse_enhanced_packet(&body)?),
+            PCAPNG_BLOCK_SPB => PcapngBlock::Packet(self.parse_simple_packet(&body)?),
+            PCAPNG_BLOCK_OPB => PcapngBlock::Packet(self.parse_obsolete_packet(&body)?),
+            PCAPNG_BLOCK_NRB => PcapngBlock::NameResolution(self.parse_name_resolution(&body)),
+            PCAPNG_BLOCK_ISB => PcapngBlock::InterfaceStatistics(self.parse_statistics(&body)?),
+            PCAPNG_BLOCK_CUSTOM | PCAPNG_BLOCK_CUSTOM_NOCOPY if body.len() >= 4 => PcapngBlock::Custom {
+                pen: e.read_u32(&body),
+                data: body[4..].to_vec(),
+                copiable: block_type == PCAPNG_BLOCK_CUSTOM,
+            },
+            _ => PcapngBlock::Unknown { block_type, body },
+        };
+
+        Ok(Some(block))
+    }
+
+    fn parse_interface(&self, body: &[u8]) -> PResult<InterfaceDescription> {
+        let e = self.section.endianness;
+
+        if body.len() < 8 {
+            return Err(invalid_block(PCAPNG_BLOCK_IDB));
+        }
+
+        let mut iface = InterfaceDescription::new(DataLink::from_linktype(e.read_u16(body) as u32), e.read_u32(&body[4..]));
+
+        for (code, value) in options(e, &body[8..]) {
+            match code {
+                OPT_COMMENT => iface.comment = option_string(value),
+                IF_NAME => iface.name = option_string(value),
+                IF_DESCRIPTION => iface.description = option_string(value),
+                IF_TSRESOL if !value.is_empty() => iface.tsresol = value[0],
+                IF_FILTER if value.first() == Some(&0) => iface.filter = option_string(&value[1..]),
+                _ => {},
+            }
+        }
+
+        Ok(iface)
+    }
+
+    fn packet_interface(&self, interface_id: u32) -> PResult<&InterfaceDescription> {
+        self.interface(interface_id).ok_or(LibPcapError::InvalidRecord {
+            msg: format!("unknown interface id {interface_id}"),
+        })
+    }
+
+    fn parse_enhanced_packet(&self, body: &[u8]) -> PResult<PcapngPacket> {
+        let e = self.section.endianness;
+
+        if body.len() < 20 {
+            return Err(invalid_block(PCAPNG_BLOCK_EPB));
+        }
+
+        let interface_id = e.read_u32(body);
+        let units = (e.read_u32(&body[4..]) as u64) << 32 | e.read_u32(&body[8..]) as u64;
+        let caplen = e.read_u32(&body[12..]) as usize;
+        let len = e.read_u32(&body[16..]);
+        let data = body.get(20..20 + caplen).ok_or(invalid_block(PCAPNG_BLOCK_EPB))?;
+        let ts = self.packet_interface(interface_id)?.units_to_ts(units);
+        let mut options = PacketOptions::default();
+
+        for (code, value) in self::options(e, body.get(20 + ((caplen + 3) & !3)..).unwrap_or_default()) {
+            match code {
+                OPT_COMMENT => options.comment = option_string(value),
+                EPB_FLAGS if value.len() >= 4 => options.flags = Some(e.read_u32(value)),
+                EPB_HASH => options.hash = Some(value.to_vec()),
+                EPB_DROPCOUNT => options.dropcount = option_u64(e, value),
+                _ => {},
+            }
+        }
+
+        Ok(PcapngPacket {
+            interface_id,
+            packet: Packet::new(data.to_vec(), ts, len),
+            options,
+        })
+    }
+
+    fn parse_simple_packet(&self, body: &[u8]) -> PResult<PcapngPacket> {
+        let e = self.section.endianness;
+
+        if body.len() < 4 {
+            return Err(invalid_block(PCAPNG_BLOCK_SPB));
+        }
+
+        let len = e.read_u32(body);
+        let snaplen = match self.packet_interface(0)?.snaplen {
+            0 => u32::MAX,
+            v => v,
+        };
+        let caplen = (len.min(snaplen) as usize).min(body.len() - 4);
+
+        Ok(PcapngPacket {
+            interface_id: 0,
+            packet: Packet::new(body[4..4 + caplen].to_vec(), Duration::ZERO, len),
+            options: PacketOptions::default(),
+        })
+    }
+
+    fn parse_obsolete_packet(&self, body: &[u8]) -> PResult<PcapngPacket> {
+        let e = self.section.endianness;
+
+        if body.len() < 20 {
+            return Err(invalid_block(PCAPNG_BLOCK_OPB));
+        }
+
+        let interface_id = e.read_u16(body) as u32;
+        let dropcount = e.read_u16(&body[2..]);
+        let units = (e.read_u32(&body[4..]) as u64) << 32 | e.read_u32(&body[8..]) as u64;
+        let caplen = e.read_u32(&body[12..]) as usize;
+        let len = e.read_u32(&body[16..]);
+        let data = body.get(20..20 + caplen).ok_or(invalid_block(PCAPNG_BLOCK_OPB))?;
+        let ts = self.packet_interface(interface_id)?.units_to_ts(units);
+        let mut options = PacketOptions {
+            dropcount: if dropcount == u16::MAX { None } else { Some(dropcount as u64) },
+            ..Default::default()
+        };
+
+        for (code, value) in self::options(e, body.get(20 + ((caplen + 3) & !3)..).unwrap_or_default()) {
+            match code {
+                OPT_COMMENT => options.comment = option_string(value),
+                EPB_FLAGS if value.len() >= 4 => options.flags = Some(e.read_u32(value)),
+                EPB_HASH => options.hash = Some(value.to_vec()),
+                _ => {},
+            }
+        }
+
+        Ok(PcapngPacket {
+            interface_id,
+            packet: Packet::new(data.to_vec(), ts, len),
+            options,
+        })
+    }
+
+    fn parse_name_resolution(&self, body: &[u8]) -> Vec<NameRecord> {
+        let mut records = vec![];
+
+        // Name records share the option layout
+        for (code, value) in options(self.section.endianness, body) {
+            let (addr, names) = match code {
+                NRB_RECORD_IPV4 if value.len() >= 4 => {
+                    let octets: [u8; 4] = value[..4].try_into().unwrap_or_default();
+                    (IpAddr::from(octets), &value[4..])
+                },
+                NRB_RECORD_IPV6 if value.len() >= 16 => {
+                    let octets: [u8; 16] = value[..16].try_into().unwrap_or_default();
+                    (IpAddr::from(octets), &value[16..])
+                },
+                _ => continue,
+            };
+
+            records.push(NameRecord {
+                addr,
+                names: names
+                    .split(|v| *v == 0)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| String::from_utf8_lossy(v).to_string())
+                    .collect(),
+            });
+        }
+
+        records
+    }
+
+    fn parse_statistics(&self, body: &[u8]) -> PResult<InterfaceStatistics> {
+        let e = self.section.endianness;
+
+        if body.len() < 12 {
+            return Err(invalid_block(PCAPNG_BLOCK_ISB));
+        }
+
+        let interface_id = e.read_u32(body);
+        let units = (e.read_u32(&body[4..]) as u64) << 32 | e.read_u32(&body[8..]) as u64;
+        let mut stats = InterfaceStatistics {
+            interface_id,
+            ts: self.packet_interface(interface_id)?.units_to_ts(units),
+            ifrecv: None,
+            ifdrop: None,
+            comment: None,
+        };
+
+        for (code, value) in options(e, &body[12..]) {
+            match code {
+                OPT_COMMENT => stats.comment = option_string(value),
+                ISB_IFRECV => stats.ifrecv = option_u64(e, value),
+                ISB_IFDROP => stats.ifdrop = option_u64(e, value),
+                _ => {},
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+
+impl PcapngReader<InputStream> {
+    /// Open a pcapng file, gzip/zstd/xz compressed files are decompressed on the fly, `-` reads stdin
+    pub fn open(path: &str) -> PResult<Self> {
+        Self::new(open_input(path)?)
+    }
+}
+
+
+impl<R: Read> Iterator for PcapngReader<R> {
+    type Item = PResult<PcapngPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.next_packet();
+
+        if value.is_err() {
+            self.done = true;
+        }
+
+        value.transpose()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcapng_tsresol() {
+        let ts = Duration::new(1700000000, 123456789);
+        let mut iface = InterfaceDescription::new(DataLink::En10mb, 65535);
+
+        assert_eq!(iface.ts_to_units(ts).unwrap(), 1700000000123456);
+        assert_eq!(iface.units_to_ts(1700000000123456), Duration::new(1700000000, 123456000));
+
+        iface.tsresol = 9;
+        assert_eq!(iface.units_to_ts(iface.ts_to_units(ts).unwrap()), ts);
+
+        iface.tsresol = 0x80 | 20;
+        assert_eq!(iface.ts_to_units(Duration::from_secs(3)).unwrap(), 3 << 20);
+        assert_eq!(iface.units_to_ts(3 << 20), Duration::from_secs(3));
+
+        // Out of the 64 bits timestamps
+        iface.tsresol = 0x80 | 64;
+        assert!(iface.ts_to_units(ts).is_err());
+
+        iface.tsresol = 19;
+        assert!(iface.ts_to_units(ts).is_err());
+    }
+
+    #[test]
+    fn test_pcapng_option_too_long() {
+        let mut writer = PcapngWriter::new(Vec::new(), SectionHeader::default()).unwrap();
+        let id = writer.add_interface(InterfaceDescription::new(DataLink::En10mb, 65535)).unwrap();
+        let len = writer.get_ref().len();
+
+        let options = PacketOptions { comment: Some("x".repeat(u16::MAX as usize + 1)), ..Default::default() };
+        assert!(matches!(writer.write_packet_with_options(id, b"\x00", Duration::new(1, 0), 1, &options), Err(LibPcapError::InvalidRecord { .. })));
+        assert_eq!(writer.get_ref().len(), len);
+
+        let mut iface = InterfaceDescription::new(DataLink::En10mb, 65535);
+        iface.name = Some("x".repeat(u16::MAX as usize + 1));
+        assert!(writer.add_interface(iface).is_err());
+    }
+
+    #[test]
+    fn test_pcapng_write_blocks() {
+        let section = SectionHeader { userappl: Some("test".to_string()), endianness: Endianness::Little, ..Default::default() };
+        let mut writer = PcapngWriter::new(Vec::new(), section).unwrap();
+        let eth = writer.add_interface(InterfaceDescription::new(DataLink::En10mb, 65535)).unwrap();
+        let raw = writer.add_interface(InterfaceDescription::new(DataLink::Raw, 65535).with_tstamp_precision(TstampPrecision::Nano)).unwrap();
+        let options = PacketOptions { comment: Some("hello".to_string()), flags: Some(1), dropcount: Some(2), hash: None };
+
+        writer.write_packet_with_options(eth, b"\x00\x0c\x29", Duration::new(1, 0), 60, &options).unwrap();
+        writer.write_packet(raw, b"\x45\x00\x00\x14", Duration::new(1, 1), 4).unwrap();
+        writer.write_name_resolution(&[NameRecord { addr: "10.0.0.1".parse().unwrap(), names: vec!["host".to_string()] }]).unwrap();
+        assert!(writer.write_packet(2, b"", Duration::new(1, 0), 0).is_err());
+
+        let buf = writer.into_inner();
+        let mut types = vec![];
+        let mut offset = 0;
+
+        while offset < buf.len() {
+            let block_type = Endianness::Little.read_u32(&buf[offset..]);
+            let length = Endianness::Little.read_u32(&buf[offset + 4..]) as usize;
+
+            assert_eq!(length % 4, 0);
+            assert_eq!(Endianness::Little.read_u32(&buf[offset + length - 4..]) as usize, length);
+            types.push(block_type);
+            offset += length;
+        }
+
+        assert_eq!(types, vec![PCAPNG_BLOCK_SHB, PCAPNG_BLOCK_IDB, PCAPNG_BLOCK_IDB, PCAPNG_BLOCK_EPB, PCAPNG_BLOCK_EPB, PCAPNG_BLOCK_NRB]);
+        assert_eq!(&buf[8..12], b"\x4d\x3c\x2b\x1a");
+    }
+
+    #[test]
+    fn test_pcapng_read_blocks() {
+        // SHB, IDB (eth0, tsresol 9), unknown block, EPB with a comment, written by hand in big endian
+        let input = b"\x0a\x0d\x0d\x0a\x00\x00\x00\x1c\x1a\x2b\x3c\x4d\x00\x01\x00\x00\
+        \xff\xff\xff\xff\xff\xff\xff\xff\x00\x00\x00\x1c\
+        \x00\x00\x00\x01\x00\x00\x00\x28\x00\x01\x00\x00\x00\x00\xff\xff\
+        \x00\x02\x00\x04eth0\x00\x09\x00\x01\x09\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x28\
+        \x00\x00\x01\x23\x00\x00\x00\x10\xde\xad\xbe\xef\x00\x00\x00\x10\
+        \x00\x00\x00\x06\x00\x00\x00\x30\x00\x00\x00\x00\x00\x00\x00\x00\x3b\x9a\xca\x05\
+        \x00\x00\x00\x03\x00\x00\x00\x3c\x01\x02\x03\x00\x00\x01\x00\x02hi\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x30";
+
+        let mut reader = PcapngReader::new(&input[..]).unwrap();
+        assert_eq!(reader.section().endianness, Endianness::Big);
+        assert_eq!(reader.interfaces().len(), 1);
+        assert_eq!(reader.interfaces()[0].name.as_deref(), Some("eth0"));
+        assert_eq!(reader.interfaces()[0].tsresol, 9);
+
+        assert!(matches!(reader.next_block().unwrap(), Some(PcapngBlock::Interface(_))));
+        assert_eq!(reader.next_block().unwrap(), Some(PcapngBlock::Unknown { block_type: 0x123, body: b"\xde\xad\xbe\xef".to_vec() }));
+
+        let pkt = reader.next_packet().unwrap().unwrap();
+        assert_eq!(pkt.interface_id, 0);
+        assert_eq!(pkt.packet.ts, Duration::new(1, 5));
+        assert_eq!(pkt.packet.buf, b"\x01\x02\x03");
+        assert_eq!(pkt.packet.len, 60);
+        assert_eq!(pkt.options.comment.as_deref(), Some("hi"));
+        assert_eq!(reader.next_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn test_pcapng_roundtrip() {
+        let mut buf = vec![];
+
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let section = SectionHeader { endianness, os: Some("linux".to_string()), ..Default::default() };
+            let mut writer = PcapngWriter::new(Vec::new(), section.clone()).unwrap();
+            let mut iface = InterfaceDescription::new(DataLink::LinuxSll, 262144).with_tstamp_precision(TstampPrecision::Nano);
+            iface.name = Some("any".to_string());
+            iface.description = Some("all interfaces".to_string());
+            iface.filter = Some("port 80".to_string());
+
+            writer.add_interface(InterfaceDescription::new(DataLink::En10mb, 65535)).unwrap();
+            writer.add_interface(iface.clone()).unwrap();
+
+            let options = PacketOptions {
+                comment: Some("retransmission".to_string()),
+                flags: Some(0x2),
+                hash: Some(vec![2, 0xaa, 0xbb, 0xcc, 0xdd]),
+                dropcount: Some(0x1_0000_0002),
+            };
+            let records = vec![
+                NameRecord { addr: "10.0.0.1".parse().unwrap(), names: vec!["a".to_string(), "b".to_string()] },
+                NameRecord { addr: "::1".parse().unwrap(), names: vec!["localhost".to_string()] },
+            ];
+
+            writer.write_name_resolution(&records).unwrap();
+            writer.write_packet_with_options(1, b"\x00\x04\x00\x01\x00", Duration::new(1700000000, 123456789), 1500, &options).unwrap();
+            writer.write_packet(0, b"\x00\x0c\x29", Duration::new(1700000001, 1000), 3).unwrap();
+            buf.extend(writer.into_inner());
+
+            let mut reader = PcapngReader::new(&buf[..]).unwrap();
+            let mut blocks = vec![];
+
+            while let Some(block) = reader.next_block().unwrap() {
+                blocks.push(block);
+            }
+
+            let blocks = &blocks[blocks.len() - 5..];
+            assert_eq!(blocks[0], PcapngBlock::Interface(InterfaceDescription::new(DataLink::En10mb, 65535)));
+            assert_eq!(blocks[1], PcapngBlock::Interface(iface.clone()));
+            assert_eq!(blocks[2], PcapngBlock::NameResolution(records));
+            assert_eq!(reader.section(), &section);
+
+            match &blocks[3] {
+                PcapngBlock::Packet(pkt) => {
+                    assert_eq!(pkt.interface_id, 1);
+                    assert_eq!(pkt.packet.ts, Duration::new(1700000000, 123456789));
+                    assert_eq!(pkt.packet.len, 1500);
+                    assert_eq!(pkt.options, options);
+                },
+                block => panic!("unexpected block {block:?}"),
+            }
+
+            match &blocks[4] {
+                PcapngBlock::Packet(pkt) => {
+                    assert_eq!(pkt.interface_id, 0);
+                    assert_eq!(pkt.packet.ts, Duration::new(1700000001, 1000));
+                    assert_eq!(pkt.options, PacketOptions::default());
+                },
+                block => panic!("unexpected block {block:?}"),
+            }
+        }
+
+        // Two sections, packets of both are returned
+        let reader = PcapngReader::new(&buf[..]).unwrap();
+        assert_eq!(reader.map(|v| v.unwrap().packet.buf.len()).collect::<Vec<_>>(), vec![5, 3, 5, 3]);
+    }
+}
@@ -5,8 +5,9 @@ use jppe::prelude::MacAddress;
 
 #[derive(Debug, PartialEq, Eq, BorrowByteEncode, BorrowByteDecode)]
 pub struct EthernetHeader<'a> {
-    pub smac: MacAddress,
+    /// The destination comes first on the wire
     pub dmac: MacAddress,
+    pub smac: MacAddress,
     pub r#type: u16,
     _mark: PhantomData<&'a ()>,
 }
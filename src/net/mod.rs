@@ -3,6 +3,7 @@ pub mod ipv4;
 pub mod tcp;
 pub mod udp;
 pub mod checksum;
+pub mod vlan;
 
 use std::net::IpAddr;
 
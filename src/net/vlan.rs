@@ -0,0 +1,39 @@
+use std::borrow::Cow;
+
+
+/// Length of an Ethernet header without VLAN tag
+pub const ETHERNET_HEADER_LEN: usize = 14;
+/// Length of a VLAN tag, TPID and TCI
+pub const VLAN_TAG_LEN: usize = 4;
+
+
+/// Whether an ethertype is a VLAN tag, 802.1Q, 802.1ad or the legacy QinQ one
+pub fn is_vlan_type(value: u16) -> bool {
+    matches!(value, 0x8100 | 0x88a8 | 0x9100)
+}
+
+
+/// VLAN id of the outer tag of an Ethernet frame
+pub fn vlan_id(buf: &[u8]) -> Option<u16> {
+    if buf.len() < ETHERNET_HEADER_LEN + 2 || !is_vlan_type(u16::from_be_bytes([buf[12], buf[13]])) {
+        return None;
+    }
+
+    Some(u16::from_be_bytes([buf[14], buf[15]]) & 0x0fff)
+}
+
+
+/// Ethernet frame without its VLAN tags, borrowed when untagged
+pub fn untagged(buf: &[u8]) -> Cow<'_, [u8]> {
+    if vlan_id(buf).is_none() {
+        return Cow::Borrowed(buf);
+    }
+
+    let mut value = buf.to_vec();
+
+    while vlan_id(&value).is_some() && value.len() >= ETHERNET_HEADER_LEN + VLAN_TAG_LEN {
+        value.drain(12..12 + VLAN_TAG_LEN);
+    }
+
+    Cow::Owned(value)
+}
@@ -0,0 +1,160 @@
+use std::time::Duration;
+use jkcenum::JkcEnum;
+
+
+#[derive(Debug, Default, JkcEnum, PartialEq, Eq)]
+pub enum LibPcapMode {
+    #[default]
+    #[jenum(rename="r")]
+    Read,
+    #[jenum(rename="w")]
+    Write,
+    #[jenum(rename="a")]
+    Append,
+}
+
+
+/// Capture file format
+#[derive(Debug, Default, Clone, Copy, JkcEnum, PartialEq, Eq)]
+pub enum FileFormat {
+    #[default]
+    #[jenum(rename="pcap")]
+    Pcap,
+    #[jenum(rename="pcapng")]
+    Pcapng,
+}
+
+
+/// Timestamp precision used when reading or writing a pcap file
+#[derive(Debug, Default, Clone, Copy, JkcEnum, PartialEq, Eq)]
+pub enum TstampPrecision {
+    #[default]
+    #[jenum(rename="micro")]
+    Micro,
+    #[jenum(rename="nano")]
+    Nano,
+}
+
+
+impl TstampPrecision {
+    /// `PCAP_TSTAMP_PRECISION_*` value
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Micro => 0,
+            Self::Nano => 1,
+        }
+    }
+
+    pub fn from_value(value: u32) -> Self {
+        if value == 1 { Self::Nano } else { Self::Micro }
+    }
+
+    /// Build a duration since the UNIX epoch from seconds and a fraction in this precision
+    pub fn to_duration(&self, sec: u64, frac: u32) -> Duration {
+        match self {
+            Self::Micro => Duration::new(sec, frac.saturating_mul(1_000)),
+            Self::Nano => Duration::new(sec, frac),
+        }
+    }
+
+    /// Sub-second part of a duration in this precision
+    pub fn subsec(&self, ts: Duration) -> u32 {
+        match self {
+            Self::Micro => ts.subsec_micros(),
+            Self::Nano => ts.subsec_nanos(),
+        }
+    }
+
+    /// Drop the digits this precision can't represent
+    pub fn truncate(&self, ts: Duration) -> Duration {
+        self.to_duration(ts.as_secs(), self.subsec(ts))
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibPcapPacketInfo<'a> {
+    /// Seconds since the UNIX epoch
+    pub timestamp: i64,
+    /// Full precision timestamp since the UNIX epoch
+    pub ts: Duration,
+    /// Number of bytes captured
+    pub caplen: u32,
+    /// Original length of the packet on the wire
+    pub len: u32,
+    pub buf: &'a [u8],
+}
+
+
+impl<'a> LibPcapPacketInfo<'a> {
+    /// Copy the borrowed bytes into an owned `Packet`
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use std::time::Duration;
+    /// use libpcap_rs::packet::LibPcapPacketInfo;
+    /// 
+    /// let info = LibPcapPacketInfo { timestamp: 1, ts: Duration::from_secs(1), caplen: 2, len: 60, buf: b"\x45\x00" };
+    /// let pkt = info.into_owned();
+    /// 
+    /// assert_eq!(pkt.buf, b"\x45\x00");
+    /// assert_eq!(pkt.as_info(), info);
+    /// ```
+    /// 
+    pub fn into_owned(self) -> Packet {
+        Packet {
+            timestamp: self.timestamp,
+            ts: self.ts,
+            caplen: self.caplen,
+            len: self.len,
+            buf: self.buf.to_vec(),
+        }
+    }
+}
+
+
+impl<'a> From<LibPcapPacketInfo<'a>> for Packet {
+    fn from(value: LibPcapPacketInfo<'a>) -> Self {
+        value.into_owned()
+    }
+}
+
+
+/// Packet owning its bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// Seconds since the UNIX epoch
+    pub timestamp: i64,
+    /// Full precision timestamp since the UNIX epoch
+    pub ts: Duration,
+    /// Number of bytes captured
+    pub caplen: u32,
+    /// Original length of the packet on the wire
+    pub len: u32,
+    pub buf: Vec<u8>,
+}
+
+
+impl Packet {
+    pub fn new(buf: Vec<u8>, ts: Duration, len: u32) -> Self {
+        Self {
+            timestamp: ts.as_secs() as i64,
+            ts,
+            caplen: buf.len() as u32,
+            len,
+            buf,
+        }
+    }
+
+    /// Borrow the packet as a `LibPcapPacketInfo`
+    pub fn as_info(&self) -> LibPcapPacketInfo<'_> {
+        LibPcapPacketInfo {
+            timestamp: self.timestamp,
+            ts: self.ts,
+            caplen: self.caplen,
+            len: self.len,
+            buf: &self.buf,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::io;
 use std::fs;
 use std::path::{Path, PathBuf};
+use crate::compress::Compression;
 
 
 pub fn visit_dirs<'a>(dir: &Path) -> io::Result<Vec<PathBuf>> {
@@ -18,7 +19,7 @@ pub fn visit_dirs<'a>(dir: &Path) -> io::Result<Vec<PathBuf>> {
             if path.is_dir() {
                 let value = visit_dirs(&path)?;
                 vlist.extend(value);
-            } else if path_string.ends_with(".pcap") || path_string.ends_with(".cap") || path_string.ends_with(".pcapng") {
+            } else if is_capture_file(&path_string) {
                 vlist.push(entry.path());
             }
         }
@@ -26,3 +27,53 @@ pub fn visit_dirs<'a>(dir: &Path) -> io::Result<Vec<PathBuf>> {
 
     Ok(vlist)
 }
+
+
+/// Whether a file name looks like a capture, `.pcap`, `.cap` or `.pcapng`,
+/// optionally followed by the extension of a compiled-in codec (`.gz`, `.zst`, `.xz`)
+pub fn is_capture_file(name: &str) -> bool {
+    let compression = Compression::from_path(name);
+
+    let name = match compression.extension() {
+        Some(ext) if compression.is_supported() => &name[..name.len() - ext.len() - 1],
+        _ => name,
+    };
+
+    name.ends_with(".pcap") || name.ends_with(".cap") || name.ends_with(".pcapng")
+}
+
+
+pub fn join_home<'a>(path: &'a str) -> PathBuf {
+    if let Some(path) = path.strip_prefix('~') {
+        if let Ok(home_dir) = std::env::var("HOME") {
+            return Path::new(&home_dir).join(Path::new(path));
+        }
+    }
+
+    Path::new(path).to_path_buf()
+}
+
+
+/// Insert `.{value}` before the first extension of the file name, `capture.pcap.gz` gives `capture.{value}.pcap.gz`
+pub(crate) fn insert_before_extension(path: &str, value: &str) -> String {
+    let start = path.rfind('/').map(|v| v + 1).unwrap_or_default();
+
+    match path[start..].find('.') {
+        Some(i) if i > 0 => format!("{}.{value}{}", &path[..start + i], &path[start + i..]),
+        _ => format!("{path}.{value}"),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_before_extension() {
+        assert_eq!(insert_before_extension("capture.pcap", "0"), "capture.0.pcap");
+        assert_eq!(insert_before_extension("/tmp/a.b/capture.pcap.gz", "12"), "/tmp/a.b/capture.12.pcap.gz");
+        assert_eq!(insert_before_extension("capture", "3"), "capture.3");
+        assert_eq!(insert_before_extension("dir/.hidden", "1"), "dir/.hidden.1");
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::{LibPcap, PResult};
+use crate::packet::{LibPcapPacketInfo, TstampPrecision};
+use crate::time::{has_time_specifier, strftime_utc};
+use crate::path::insert_before_extension;
+
+
+/// Size of a packet record header, used when the output size can't be told by the handle
+const RECORD_HEADER_LEN: u64 = 16;
+const FILE_HEADER_LEN: u64 = 24;
+
+
+/// Callback fired with the path of a closed file
+pub type CloseCallback = Box<dyn FnMut(&Path) + Send>;
+
+
+/// Pcap writer rolling over to a new file after a size, a packet count or a time interval
+///
+/// File names come from a strftime-style pattern (`%Y %m %d %H %M %S %s`, UTC) applied to the
+/// timestamp of the first packet of each file. When rotating by size or packet count, when the
+/// pattern has no time specifier, or when it gives the name of a file already written, e.g.
+/// `%Y%m%d` with an hourly interval, a sequence number is inserted before the extension:
+/// `capture.pcap` gives `capture.0.pcap`, `capture.1.pcap`, ...
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::LibPcap;
+/// use libpcap_rs::rotate::RotatingWriter;
+///
+/// let mut input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+/// let mut output = RotatingWriter::new("rotate-%Y%m%d-%H%M%S.pcap", input.linktype(), input.snaplen())
+///     .with_max_packets(10)
+///     .with_max_files(2)
+///     .on_close(|path| println!("closed {}", path.display()));
+///
+/// let mut iter = input.read();
+///
+/// while let Some(pkt) = iter.next_packet() {
+///     output.write_packet(&pkt).unwrap();
+/// }
+///
+/// output.close().unwrap();
+/// # for path in output.files() { std::fs::remove_file(path).unwrap(); }
+/// ```
+///
+pub struct RotatingWriter {
+    pattern: String,
+    linktype: i32,
+    snaplen: i32,
+    precision: TstampPrecision,
+    max_bytes: Option<u64>,
+    max_packets: Option<u64>,
+    interval: Option<Duration>,
+    max_files: Option<usize>,
+    on_close: Option<CloseCallback>,
+    current: Option<LibPcap>,
+    opened_at: Duration,
+    packets: u64,
+    bytes: u64,
+    sequence: u64,
+    files: VecDeque<PathBuf>,
+    /// Every path opened, never truncated by a later file
+    opened: HashSet<String>,
+}
+
+
+impl RotatingWriter {
+    /// # Args:
+    ///
+    /// - `pattern`: output path, may hold strftime-style specifiers
+    /// - `linktype`: `DLT_*` link type of the files
+    /// - `snaplen`: snapshot length of the files
+    ///
+    pub fn new(pattern: &str, linktype: i32, snaplen: i32) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            linktype,
+            snaplen,
+            precision: TstampPrecision::Micro,
+            max_bytes: None,
+            max_packets: None,
+            interval: None,
+            max_files: None,
+            on_close: None,
+            current: None,
+            opened_at: Duration::ZERO,
+            packets: 0,
+            bytes: 0,
+            sequence: 0,
+            files: VecDeque::new(),
+            opened: HashSet::new(),
+        }
+    }
+
+    pub fn with_tstamp_precision(mut self, precision: TstampPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Roll over once a file reaches `value` bytes, like tcpdump `-C`
+    pub fn with_max_bytes(mut self, value: u64) -> Self {
+        self.max_bytes = Some(value);
+        self
+    }
+
+    /// Roll over once a file holds `value` packets
+    pub fn with_max_packets(mut self, value: u64) -> Self {
+        self.max_packets = Some(value);
+        self
+    }
+
+    /// Roll over when a packet is `value` later than the first packet of the file, like tcpdump `-G`
+    pub fn with_interval(mut self, value: Duration) -> Self {
+        self.interval = Some(value);
+        self
+    }
+
+    /// Keep only the last `value` files, older ones are deleted, like tcpdump `-W`
+    pub fn with_max_files(mut self, value: usize) -> Self {
+        self.max_files = Some(value);
+        self
+    }
+
+    /// Call `f` with the path of every file once it is closed
+    pub fn on_close<F: FnMut(&Path) + Send + 'static>(mut self, f: F) -> Self {
+        self.on_close = Some(Box::new(f));
+        self
+    }
+
+    /// Path of the file being written
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().and(self.files.back().map(|v| v.as_path()))
+    }
+
+    /// Files written and still kept, oldest first
+    pub fn files(&self) -> &VecDeque<PathBuf> {
+        &self.files
+    }
+
+    /// Write a packet read from a pcap file or a capture
+    pub fn write_packet(&mut self, pkt: &LibPcapPacketInfo<'_>) -> PResult<()> {
+        self.write_ts_with_len(pkt.buf, pkt.ts, pkt.len)
+    }
+
+    /// Write a packet, rolling over to a new file first when the interval elapsed
+    pub fn write_ts_with_len(&mut self, buf: &[u8], ts: Duration, len: u32) -> PResult<()> {
+        if let Some(interval) = self.interval {
+            if self.current.is_some() && ts >= self.opened_at + interval {
+                self.close()?;
+            }
+        }
+
+        if self.current.is_none() {
+            self.open_next(ts)?;
+        }
+
+        if let Some(current) = &self.current {
+            current.write_ts_with_len(buf, ts, len)?;
+
+            self.packets += 1;
+            self.bytes += RECORD_HEADER_LEN + buf.len() as u64;
+
+            let size = current.position().unwrap_or(self.bytes);
+
+            if self.max_bytes.map(|v| size >= v).unwrap_or_default()
+                || self.max_packets.map(|v| self.packets >= v).unwrap_or_default()
+            {
+                self.close()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close the file being written and call the close callback, the next packet opens a new file
+    /// 
+    /// The callback isn't called when the file failed to be written completely.
+    pub fn close(&mut self) -> PResult<()> {
+        if let Some(mut current) = self.current.take() {
+            current.close()?;
+
+            if let (Some(f), Some(path)) = (&mut self.on_close, self.files.back()) {
+                f(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open_next(&mut self, ts: Duration) -> PResult<()> {
+        let mut path = strftime_utc(&self.pattern, ts);
+
+        if self.max_bytes.is_some()
+            || self.max_packets.is_some()
+            || !has_time_specifier(&self.pattern)
+            || self.opened.contains(&path)
+        {
+            path = insert_before_extension(&path, &self.sequence.to_string());
+        }
+
+        self.sequence += 1;
+        self.opened.insert(path.clone());
+
+        let current = LibPcap::open_with_header(&path, "w", self.linktype, self.snaplen, self.precision)?;
+
+        self.current = Some(current);
+        self.opened_at = ts;
+        self.packets = 0;
+        self.bytes = FILE_HEADER_LEN;
+        self.files.push_back(PathBuf::from(path));
+
+        if let Some(max_files) = self.max_files {
+            while self.files.len() > max_files.max(1) {
+                if let Some(path) = self.files.pop_front() {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+impl Drop for RotatingWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
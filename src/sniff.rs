@@ -1,27 +1,472 @@
+use std::any::Any;
 use std::ffi::{CString, CStr};
-use std::marker::PhantomData;
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Waker;
+use jkcenum::JkcEnum;
 use crate::PResult;
 use crate::libpcap::{
-    pcap_t, pcap_stat,
+    pcap_t, pcap_stat, pcap_pkthdr, pcap_handler, u_char, PCAP_ERROR, PCAP_ERROR_BREAK, PCAP_ERRBUF_SIZE,
+    PCAP_ERROR_ACTIVATED, PCAP_ERROR_NO_SUCH_DEVICE, PCAP_ERROR_RFMON_NOTSUP, PCAP_ERROR_NOT_RFMON,
+    PCAP_ERROR_PERM_DENIED, PCAP_ERROR_IFACE_NOT_UP, PCAP_ERROR_CANTSET_TSTAMP_TYPE,
+    PCAP_ERROR_PROMISC_PERM_DENIED, PCAP_ERROR_TSTAMP_PRECISION_NOTSUP,
+    PCAP_WARNING_PROMISC_NOTSUP, PCAP_WARNING_TSTAMP_TYPE_NOTSUP, pcap_statustostr,
     pcap_create, pcap_activate, pcap_geterr,
     pcap_set_snaplen, pcap_set_promisc, pcap_set_timeout,
     pcap_set_immediate_mode, pcap_set_buffer_size, pcap_set_datalink,
     pcap_set_rfmon, pcap_set_tstamp_precision, pcap_set_tstamp_type, pcap_close,
-    pcap_next, pcap_stats,
+    pcap_stats, pcap_get_tstamp_precision, pcap_datalink, pcap_snapshot,
+    pcap_list_datalinks, pcap_free_datalinks, pcap_loop, pcap_dispatch, pcap_breakloop,
+    pcap_next_ex, pcap_setnonblock, pcap_getnonblock, pcap_get_selectable_fd,
+    pcap_setdirection, pcap_sendpacket, pcap_inject, pcap_direction_t,
+    pcap_direction_t_PCAP_D_INOUT, pcap_direction_t_PCAP_D_IN, pcap_direction_t_PCAP_D_OUT,
 };
-use crate::time::now_timestamp;
-use crate::errors::LibPcapError;
+use crate::make_cstr;
+use crate::time::now_duration;
+use crate::errors::{LibPcapError, PcapErrorKind};
 use crate::wrapper::{libpcap_set_filter, get_first_iface};
-use crate::wrapper::LibPcapPacketInfo;
+use crate::wrapper::{LibPcapPacketInfo, Packet, TstampPrecision, DataLink, timeval_to_duration};
 
 
 type PcapStat = pcap_stat;
 
 
+/// Owner of a `pcap_t`, closed once the `Sniff` and all its `BreakHandle`s are dropped
+#[derive(Debug)]
+struct PcapHandle {
+    pcap: *mut pcap_t,
+    /// Set by `BreakHandle::breakloop` until a `SniffStream` sees it
+    broken: AtomicBool,
+    /// Task of a `SniffStream` waiting for packets, woken by `BreakHandle::breakloop`
+    waker: Mutex<Option<Waker>>,
+}
+
+
+unsafe impl std::marker::Sync for PcapHandle { }
+unsafe impl std::marker::Send for PcapHandle { }
+
+
+impl Drop for PcapHandle {
+    fn drop(&mut self) {
+        if !self.pcap.is_null() {
+            unsafe { pcap_close(self.pcap) };
+        }
+    }
+}
+
+
 #[derive(Debug)]
 pub struct Sniff {
     pub iface: String,
     handle: *mut pcap_t,
+    owner: Arc<PcapHandle>,
+    warnings: Vec<SniffWarning>,
+}
+
+
+/// Non fatal problem reported while opening a capture device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SniffWarning {
+    /// `PCAP_WARNING_PROMISC_NOTSUP`, promiscuous mode was requested but isn't supported
+    PromiscNotSupported(String),
+    /// `PCAP_WARNING_TSTAMP_TYPE_NOTSUP`, the requested timestamp type isn't supported
+    TstampTypeNotSupported(String),
+    /// `PCAP_WARNING`
+    Other(String),
+}
+
+
+/// Direction of the packets to capture, see `pcap_setdirection`
+#[derive(Debug, Default, Clone, Copy, JkcEnum, PartialEq, Eq)]
+pub enum Direction {
+    /// Received by the interface
+    #[jenum(rename="in")]
+    In,
+    /// Sent by the interface
+    #[jenum(rename="out")]
+    Out,
+    #[default]
+    #[jenum(rename="inout")]
+    InOut,
+}
+
+
+impl Direction {
+    /// `PCAP_D_*` value
+    pub fn value(&self) -> pcap_direction_t {
+        match self {
+            Self::In => pcap_direction_t_PCAP_D_IN,
+            Self::Out => pcap_direction_t_PCAP_D_OUT,
+            Self::InOut => pcap_direction_t_PCAP_D_INOUT,
+        }
+    }
+}
+
+
+/// Capture device options, applied between `pcap_create` and `pcap_activate`
+///
+/// Options set on an activated device are rejected by libpcap, so they are collected here and
+/// every failure is reported as `LibPcapError::SetOptionError` or `LibPcapError::ActivateError`.
+/// Warnings of the activation are kept in `Sniff::warnings`.
+///
+/// # Example:
+///
+/// ```no_run
+/// use libpcap_rs::sniff::SniffBuilder;
+///
+/// let sniff = SniffBuilder::new("eth0")
+///     .with_snaplen(1500)
+///     .with_promisc(true)
+///     .with_buffer_size(16 * 1024 * 1024)
+///     .with_filter("tcp port 80")
+///     .open()
+///     .unwrap();
+///
+/// for warning in sniff.warnings() {
+///     eprintln!("{warning:?}");
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct SniffBuilder {
+    iface: String,
+    snaplen: i32,
+    promisc: bool,
+    timeout: i32,
+    immediate_mode: bool,
+    buffer_size: Option<i32>,
+    rfmon: bool,
+    tstamp_type: Option<i32>,
+    tstamp_precision: Option<TstampPrecision>,
+    datalink: Option<DataLink>,
+    filter: Option<String>,
+    direction: Option<Direction>,
+    nonblock: bool,
+}
+
+
+impl SniffBuilder {
+    /// # Args:
+    ///
+    /// - `iface`: Network port name.
+    ///
+    pub fn new(iface: &str) -> Self {
+        Self {
+            iface: iface.to_string(),
+            snaplen: 65535,
+            promisc: false,
+            timeout: 0,
+            immediate_mode: true,
+            buffer_size: None,
+            rfmon: false,
+            tstamp_type: None,
+            tstamp_precision: None,
+            datalink: None,
+            filter: None,
+            direction: None,
+            nonblock: false,
+        }
+    }
+
+    /// Builder of the first active network port
+    pub fn lookup() -> PResult<Self> {
+        Ok(Self::new(&get_first_iface()?))
+    }
+
+    pub fn with_snaplen(mut self, value: i32) -> Self {
+        self.snaplen = value;
+        self
+    }
+
+    pub fn with_promisc(mut self, value: bool) -> Self {
+        self.promisc = value;
+        self
+    }
+
+    /// Read timeout in milliseconds, 0 waits forever
+    pub fn with_timeout(mut self, value: i32) -> Self {
+        self.timeout = value;
+        self
+    }
+
+    /// Deliver packets as soon as they arrive, on by default
+    pub fn with_immediate_mode(mut self, value: bool) -> Self {
+        self.immediate_mode = value;
+        self
+    }
+
+    /// Kernel buffer size in bytes
+    pub fn with_buffer_size(mut self, value: i32) -> Self {
+        self.buffer_size = Some(value);
+        self
+    }
+
+    /// Monitor mode of Wi-Fi devices
+    pub fn with_rfmon(mut self, value: bool) -> Self {
+        self.rfmon = value;
+        self
+    }
+
+    /// `PCAP_TSTAMP_*` timestamp type
+    pub fn with_tstamp_type(mut self, value: i32) -> Self {
+        self.tstamp_type = Some(value);
+        self
+    }
+
+    pub fn with_tstamp_precision(mut self, value: TstampPrecision) -> Self {
+        self.tstamp_precision = Some(value);
+        self
+    }
+
+    /// Link type, set once the device is activated
+    pub fn with_datalink<T: Into<DataLink>>(mut self, value: T) -> Self {
+        self.datalink = Some(value.into());
+        self
+    }
+
+    /// BPF filter, set once the device is activated
+    pub fn with_filter(mut self, value: &str) -> Self {
+        self.filter = Some(value.to_string());
+        self
+    }
+
+    /// Capture direction, set once the device is activated
+    pub fn with_direction(mut self, value: Direction) -> Self {
+        self.direction = Some(value);
+        self
+    }
+
+    /// Non-blocking mode, set once the device is activated
+    pub fn with_nonblock(mut self, value: bool) -> Self {
+        self.nonblock = value;
+        self
+    }
+
+    /// Create, configure and activate the capture device
+    pub fn open(self) -> PResult<Sniff> {
+        let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
+        let iface_cstring = CString::new(self.iface.as_str()).map_err(|_| LibPcapError::InvalidInterface {
+            iface: self.iface.clone(),
+            msg: "interface name contains a NUL byte".to_string(),
+        })?;
+
+        let handle = unsafe { pcap_create(iface_cstring.as_ptr(), errbuf.as_mut_ptr()) };
+
+        if handle.is_null() {
+            return Err(LibPcapError::InvalidInterface {
+                iface: self.iface,
+                msg: make_cstr!(errbuf.as_ptr()),
+            });
+        }
+
+        let owner = Arc::new(PcapHandle { pcap: handle, broken: AtomicBool::new(false), waker: Mutex::new(None) });
+        let mut warnings = vec![];
+
+        let set_option = |option: &str, code: i32| {
+            if code >= 0 {
+                return Ok(code);
+            }
+
+            Err(LibPcapError::SetOptionError {
+                option: option.to_string(),
+                kind: error_kind(code),
+                msg: status_message(handle, code),
+            })
+        };
+
+        set_option("snaplen", unsafe { pcap_set_snaplen(handle, self.snaplen) })?;
+        set_option("promisc", unsafe { pcap_set_promisc(handle, self.promisc as i32) })?;
+        set_option("timeout", unsafe { pcap_set_timeout(handle, self.timeout) })?;
+        set_option("immediate_mode", unsafe { pcap_set_immediate_mode(handle, self.immediate_mode as i32) })?;
+
+        if let Some(value) = self.buffer_size {
+            set_option("buffer_size", unsafe { pcap_set_buffer_size(handle, value) })?;
+        }
+
+        if self.rfmon {
+            set_option("rfmon", unsafe { pcap_set_rfmon(handle, 1) })?;
+        }
+
+        if let Some(value) = self.tstamp_type {
+            let code = set_option("tstamp_type", unsafe { pcap_set_tstamp_type(handle, value) })?;
+
+            if code > 0 {
+                warnings.push(warning(handle, code));
+            }
+        }
+
+        if let Some(value) = self.tstamp_precision {
+            set_option("tstamp_precision", unsafe { pcap_set_tstamp_precision(handle, value.value() as i32) })?;
+        }
+
+        let code = unsafe { pcap_activate(handle) };
+
+        if code < 0 {
+            return Err(LibPcapError::ActivateError {
+                iface: self.iface,
+                kind: error_kind(code),
+                msg: status_message(handle, code),
+            });
+        }
+
+        if code > 0 {
+            warnings.push(warning(handle, code));
+        }
+
+        let sniff = Sniff { iface: self.iface, handle, owner, warnings };
+
+        if let Some(value) = self.datalink {
+            set_option("datalink", unsafe { pcap_set_datalink(handle, value.value()) })?;
+        }
+
+        if let Some(value) = self.filter {
+            sniff.with_filter(value)?;
+        }
+
+        if let Some(value) = self.direction {
+            sniff.with_direction(value)?;
+        }
+
+        if self.nonblock {
+            sniff.with_nonblock(true)?;
+        }
+
+        Ok(sniff)
+    }
+}
+
+
+fn error_kind(code: i32) -> PcapErrorKind {
+    match code {
+        PCAP_ERROR => PcapErrorKind::Generic,
+        PCAP_ERROR_ACTIVATED => PcapErrorKind::Activated,
+        PCAP_ERROR_NO_SUCH_DEVICE => PcapErrorKind::NoSuchDevice,
+        PCAP_ERROR_RFMON_NOTSUP => PcapErrorKind::RfmonNotSupported,
+        PCAP_ERROR_NOT_RFMON => PcapErrorKind::NotRfmon,
+        PCAP_ERROR_PERM_DENIED => PcapErrorKind::PermissionDenied,
+        PCAP_ERROR_IFACE_NOT_UP => PcapErrorKind::IfaceNotUp,
+        PCAP_ERROR_CANTSET_TSTAMP_TYPE => PcapErrorKind::CantSetTstampType,
+        PCAP_ERROR_PROMISC_PERM_DENIED => PcapErrorKind::PromiscPermissionDenied,
+        PCAP_ERROR_TSTAMP_PRECISION_NOTSUP => PcapErrorKind::TstampPrecisionNotSupported,
+        code => PcapErrorKind::Other(code),
+    }
+}
+
+
+/// Message of a status code, libpcap only fills its error buffer for some of them
+fn status_message(handle: *mut pcap_t, code: i32) -> String {
+    let msg = get_pcap_error(handle);
+
+    if msg.is_empty() || matches!(code, PCAP_ERROR_ACTIVATED | PCAP_ERROR_CANTSET_TSTAMP_TYPE | PCAP_ERROR_TSTAMP_PRECISION_NOTSUP) {
+        make_cstr!(pcap_statustostr(code))
+    }
+    else {
+        msg
+    }
+}
+
+
+fn warning(handle: *mut pcap_t, code: i32) -> SniffWarning {
+    match code as u32 {
+        PCAP_WARNING_PROMISC_NOTSUP => SniffWarning::PromiscNotSupported(get_pcap_error(handle)),
+        PCAP_WARNING_TSTAMP_TYPE_NOTSUP => SniffWarning::TstampTypeNotSupported(make_cstr!(pcap_statustostr(code))),
+        _ => SniffWarning::Other(get_pcap_error(handle)),
+    }
+}
+
+
+/// Stop the `capture_loop` or `dispatch` of a `Sniff` from another thread or a signal handler
+///
+/// Wraps `pcap_breakloop`. The capture returns after the packet being processed, a loop blocked
+/// waiting for packets may only notice it at the next packet or read timeout with old libpcap versions.
+///
+/// # Example:
+///
+/// ```no_run
+/// use libpcap_rs::Sniff;
+///
+/// let mut sniff = Sniff::open("eth0").unwrap();
+/// let breaker = sniff.break_handle();
+///
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(10));
+///     breaker.breakloop();
+/// });
+///
+/// let count = sniff.capture_loop(-1, |pkt| println!("{pkt:?}")).unwrap();
+///
+/// println!("{count} packets captured");
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct BreakHandle {
+    owner: Arc<PcapHandle>,
+}
+
+
+impl BreakHandle {
+    /// Make the running or next capture loop return, a `SniffStream` of the device ends
+    pub fn breakloop(&self) {
+        unsafe { pcap_breakloop(self.owner.pcap) };
+
+        self.owner.broken.store(true, Ordering::SeqCst);
+
+        if let Some(waker) = self.owner.waker.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            waker.wake();
+        }
+    }
+}
+
+
+/// Outcome of `Sniff::read_next`
+pub(crate) enum NextPacket<'a> {
+    Packet(LibPcapPacketInfo<'a>),
+    /// Read timeout expired, or nothing to read in non-blocking mode
+    NotReady,
+    /// `BreakHandle::breakloop` was called
+    Break,
+}
+
+
+/// State shared with `packet_handler` during `pcap_loop` or `pcap_dispatch`
+struct LoopState<F> {
+    handle: *mut pcap_t,
+    precision: TstampPrecision,
+    callback: F,
+    count: usize,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+
+unsafe extern "C" fn packet_handler<F>(user: *mut u_char, header: *const pcap_pkthdr, data: *const u_char)
+where
+    F: FnMut(LibPcapPacketInfo<'_>),
+{
+    let state = &mut *(user as *mut LoopState<F>);
+
+    if state.panic.is_some() || header.is_null() {
+        return;
+    }
+
+    let header = &*header;
+    let buf = if data.is_null() { &[][..] } else { std::slice::from_raw_parts(data, header.caplen as usize) };
+    let pkt = LibPcapPacketInfo {
+        timestamp: header.ts.tv_sec,
+        ts: timeval_to_duration(&header.ts, state.precision),
+        caplen: header.caplen,
+        len: header.len,
+        buf,
+    };
+
+    // Unwinding through libpcap is undefined, the panic is resumed once the loop returned
+    match catch_unwind(AssertUnwindSafe(|| (state.callback)(pkt))) {
+        Ok(()) => state.count += 1,
+        Err(e) => {
+            state.panic = Some(e);
+            pcap_breakloop(state.handle);
+        },
+    }
 }
 
 
@@ -37,7 +482,7 @@ unsafe impl std::marker::Send for Sniff { }
 
 
 impl Sniff {
-    /// Open capture device
+    /// Open capture device with the default options, see `SniffBuilder` to change them
     /// 
     /// # Args:
     /// 
@@ -46,38 +491,17 @@ impl Sniff {
     where
         T: AsRef<[u8]>,
     {
-        let mut errbuf = String::new();
-        let iface_cstring = CString::new(iface.as_ref()).unwrap_or_default();
-
-        let handle = unsafe { pcap_create(iface_cstring.as_ptr(), errbuf.as_mut_ptr() as *mut i8) };
-
-        if handle.is_null() {
-            return Err(LibPcapError::InvalidInterface {
-                iface: iface_cstring.to_string_lossy().to_string(),
-                msg: errbuf,
-            });
-        }
-
-        unsafe {
-            pcap_set_snaplen(handle, 65535);
-            pcap_set_promisc(handle, 0);
-            pcap_set_timeout(handle, 0);
-            pcap_set_immediate_mode(handle, 1);
-        }
-
-        let activate = unsafe { pcap_activate(handle) };
+        SniffBuilder::new(&String::from_utf8_lossy(iface.as_ref())).open()
+    }
 
-        if activate != 0 {
-            return Err(LibPcapError::InvalidInterface {
-                iface: iface_cstring.to_string_lossy().to_string(),
-                msg: get_pcap_error(handle),
-            });
-        }
+    /// Builder to set the options of a capture device before it is activated
+    pub fn builder(iface: &str) -> SniffBuilder {
+        SniffBuilder::new(iface)
+    }
 
-        Ok(Self {
-            iface: iface_cstring.to_string_lossy().to_string(),
-            handle,
-        })
+    /// Warnings reported when the device was activated
+    pub fn warnings(&self) -> &[SniffWarning] {
+        &self.warnings
     }
 
     /// Find the active network port and open the device
@@ -88,6 +512,7 @@ impl Sniff {
     }
 
     /// Set snaplen
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_snaplen`")]
     pub fn with_snaplen(&self, snaplen: i32) -> &Self {
         unsafe { pcap_set_snaplen(self.handle, snaplen) };
 
@@ -105,6 +530,7 @@ impl Sniff {
     }
 
     /// Set promiscuous
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_promisc`")]
     pub fn with_promisc(&self, value: i32) -> &Self {
         unsafe { pcap_set_promisc(self.handle, value) };
 
@@ -112,6 +538,7 @@ impl Sniff {
     }
 
     /// Set timeout
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_timeout`")]
     pub fn with_timeout(&self, value: i32) -> &Self {
         unsafe { pcap_set_timeout(self.handle, value) };
 
@@ -119,6 +546,7 @@ impl Sniff {
     }
 
     /// Set immediate mode
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_immediate_mode`")]
     pub fn with_immediate_mode(&self, value: i32) -> &Self {
         unsafe { pcap_set_immediate_mode(self.handle, value) };
 
@@ -126,20 +554,33 @@ impl Sniff {
     }
 
     /// Set buffer size
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_buffer_size`")]
     pub fn with_buffer_size(&self, value: i32) -> &Self {
         unsafe { pcap_set_buffer_size(self.handle, value) };
 
         &self
     }
 
-    /// Set data link
-    pub fn with_datalink(&self, value: i32) -> &Self {
-        unsafe { pcap_set_datalink(self.handle, value) };
+    /// Set data link, one of `list_datalinks`
+    pub fn with_datalink<T>(&self, value: T) -> PResult<&Self>
+    where
+        T: Into<DataLink>,
+    {
+        let code = unsafe { pcap_set_datalink(self.handle, value.into().value()) };
 
-        &self
+        if code < 0 {
+            return Err(LibPcapError::SetOptionError {
+                option: "datalink".to_string(),
+                kind: error_kind(code),
+                msg: status_message(self.handle, code),
+            });
+        }
+
+        Ok(self)
     }
 
     /// Set rfmon
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_rfmon`")]
     pub fn with_rfmon(&self, value: i32) -> &Self {
         unsafe { pcap_set_rfmon(self.handle, value) };
 
@@ -147,6 +588,7 @@ impl Sniff {
     }
 
     /// Set timestamp precision
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_tstamp_precision`")]
     pub fn with_tstamp_precision(&self, value: i32) -> &Self {
         unsafe { pcap_set_tstamp_precision(self.handle, value) };
 
@@ -154,24 +596,301 @@ impl Sniff {
     }
 
     /// Set timestamp type
+    #[deprecated(note = "libpcap rejects it once the device is activated, use `SniffBuilder::with_tstamp_type`")]
     pub fn with_tstamp_type(&self, value: i32) -> &Self {
         unsafe { pcap_set_tstamp_type(self.handle, value) };
 
         &self
     }
 
+    /// Capture only the packets received, sent or both, not supported by every platform and device
+    pub fn with_direction(&self, value: Direction) -> PResult<&Self> {
+        let code = unsafe { pcap_setdirection(self.handle, value.value()) };
+
+        if code < 0 {
+            return Err(LibPcapError::SetOptionError {
+                option: "direction".to_string(),
+                kind: error_kind(code),
+                msg: status_message(self.handle, code),
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Send a raw packet on the capture device, like `pcap_sendpacket`
+    ///
+    /// The packet must hold the link-layer header of `datalink`.
+    ///
+    /// # Args:
+    ///
+    /// - `buf`: Raw packet data.
+    ///
+    /// # Returns:
+    ///
+    /// - Number of bytes written, the whole packet
+    ///
+    pub fn send(&self, buf: &[u8]) -> PResult<usize> {
+        if unsafe { pcap_sendpacket(self.handle, buf.as_ptr(), buf.len() as i32) } < 0 {
+            return Err(self.send_error());
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Send a raw packet on the capture device, like `pcap_inject`
+    ///
+    /// # Args:
+    ///
+    /// - `buf`: Raw packet data.
+    ///
+    /// # Returns:
+    ///
+    /// - Number of bytes written
+    ///
+    pub fn inject(&self, buf: &[u8]) -> PResult<usize> {
+        let ret = unsafe { pcap_inject(self.handle, buf.as_ptr() as *const std::os::raw::c_void, buf.len()) };
+
+        if ret < 0 {
+            return Err(self.send_error());
+        }
+
+        Ok(ret as usize)
+    }
+
+    fn send_error(&self) -> LibPcapError {
+        LibPcapError::SendPacketError {
+            iface: self.iface.clone(),
+            msg: get_pcap_error(self.handle),
+        }
+    }
+
+    /// Set non-blocking mode, `next_packet` and `dispatch` then return at once when no packet is ready
+    pub fn with_nonblock(&self, value: bool) -> PResult<&Self> {
+        let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
+
+        if unsafe { pcap_setnonblock(self.handle, value as i32, errbuf.as_mut_ptr()) } < 0 {
+            return Err(LibPcapError::CaptureError { msg: make_cstr!(errbuf.as_ptr()) });
+        }
+
+        Ok(self)
+    }
+
+    /// Whether the capture device is in non-blocking mode
+    pub fn is_nonblock(&self) -> PResult<bool> {
+        let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
+        let value = unsafe { pcap_getnonblock(self.handle, errbuf.as_mut_ptr()) };
+
+        if value < 0 {
+            return Err(LibPcapError::CaptureError { msg: make_cstr!(errbuf.as_ptr()) });
+        }
+
+        Ok(value != 0)
+    }
+
+    /// File descriptor to wait for packets with `select`, `poll`, `epoll` or an async reactor,
+    /// `None` when the device has none
+    ///
+    /// Use it with non-blocking mode, readiness of the descriptor doesn't guarantee a packet.
+    #[cfg(unix)]
+    pub fn selectable_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        let fd = unsafe { pcap_get_selectable_fd(self.handle) };
+
+        if fd < 0 { None } else { Some(fd) }
+    }
+
+    /// Read the next packet, like `pcap_next_ex`
+    ///
+    /// Returns `Ok(None)` when no packet arrived before the read timeout, when none is ready
+    /// in non-blocking mode or after `BreakHandle::breakloop`. The packet is valid until the next read.
+    pub fn next_packet(&mut self) -> PResult<Option<LibPcapPacketInfo<'_>>> {
+        match self.read_next()? {
+            NextPacket::Packet(pkt) => Ok(Some(pkt)),
+            NextPacket::NotReady | NextPacket::Break => Ok(None),
+        }
+    }
+
+    /// Wake `waker` on the next `BreakHandle::breakloop`
+    #[cfg(all(feature = "async", unix))]
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        *self.owner.waker.lock().unwrap_or_else(|e| e.into_inner()) = Some(waker.clone());
+    }
+
+    /// Whether `BreakHandle::breakloop` was called since the last time, even with no packet read since
+    pub(crate) fn take_break(&self) -> bool {
+        self.owner.broken.swap(false, Ordering::SeqCst)
+    }
+
+    /// `next_packet` telling a `breakloop` apart from no packet being ready
+    pub(crate) fn read_next(&mut self) -> PResult<NextPacket<'_>> {
+        let mut header = std::ptr::null_mut();
+        let mut data = std::ptr::null();
+
+        let ret = unsafe { pcap_next_ex(self.handle, &mut header, &mut data) };
+
+        if ret == PCAP_ERROR {
+            return Err(LibPcapError::CaptureError { msg: get_pcap_error(self.handle) });
+        }
+
+        if ret == PCAP_ERROR_BREAK {
+            self.take_break();
+            return Ok(NextPacket::Break);
+        }
+
+        if ret != 1 || header.is_null() || data.is_null() {
+            return Ok(NextPacket::NotReady);
+        }
+
+        let header = unsafe { &*header };
+        let precision = TstampPrecision::from_value(unsafe { pcap_get_tstamp_precision(self.handle) } as u32);
+
+        Ok(NextPacket::Packet(LibPcapPacketInfo {
+            timestamp: header.ts.tv_sec,
+            ts: timeval_to_duration(&header.ts, precision),
+            caplen: header.caplen,
+            len: header.len,
+            buf: unsafe { std::slice::from_raw_parts(data, header.caplen as usize) },
+        }))
+    }
+
+    /// `DLT_*` link type of the capture device
+    pub fn linktype(&self) -> i32 {
+        unsafe { pcap_datalink(self.handle) }
+    }
+
+    /// Link type of the capture device
+    pub fn datalink(&self) -> DataLink {
+        DataLink::from(self.linktype())
+    }
+
+    /// Link types supported by the capture device
+    pub fn list_datalinks(&self) -> PResult<Vec<DataLink>> {
+        let mut dlt_buf = std::ptr::null_mut();
+
+        let count = unsafe { pcap_list_datalinks(self.handle, &mut dlt_buf) };
+
+        if count < 0 {
+            return Err(LibPcapError::ListDataLinksError {
+                msg: get_pcap_error(self.handle),
+            });
+        }
+
+        let datalinks = unsafe { std::slice::from_raw_parts(dlt_buf, count as usize) }
+            .iter()
+            .map(|v| DataLink::from(*v))
+            .collect();
+
+        unsafe { pcap_free_datalinks(dlt_buf) };
+
+        Ok(datalinks)
+    }
+
+    /// Snapshot length of the capture device
+    pub fn snaplen(&self) -> i32 {
+        unsafe { pcap_snapshot(self.handle) }
+    }
+
     /// Capture data packet
     /// 
+    /// The device is borrowed mutably, the packets of libpcap's buffer can't outlive the next read:
+    /// 
+    /// ```compile_fail
+    /// use libpcap_rs::Sniff;
+    /// 
+    /// let mut sniff = Sniff::open("lo").unwrap();
+    /// let mut a = sniff.capture(10);
+    /// let mut b = sniff.capture(10);
+    /// 
+    /// let pkt = a.next_packet();
+    /// b.next_packet();
+    /// println!("{pkt:?}");
+    /// ```
+    /// 
     /// # Args:
     /// 
-    /// - `count`: Number of captured packets.
+    /// - `count`: Number of captured packets, `-1` for no limit.
     /// 
     /// # Returns:
     /// 
     /// - `SniffIterator`
     /// 
-    pub fn capture(&self, count: isize) -> SniffIterator<'_> {
-        SniffIterator::new(self.handle, count)
+    pub fn capture(&mut self, count: isize) -> SniffIterator<'_> {
+        SniffIterator::new(self, count)
+    }
+
+    /// Handle to stop `capture_loop` or `dispatch`, it keeps the device open
+    pub fn break_handle(&self) -> BreakHandle {
+        BreakHandle { owner: self.owner.clone() }
+    }
+
+    /// Capture packets with a callback, like `pcap_loop`
+    ///
+    /// Returns after `count` packets, `-1` or `0` for no limit, or when `BreakHandle::breakloop` is called.
+    /// A panic in the callback stops the capture and is resumed by this function.
+    /// The device is borrowed for the whole loop, so the callback can't re-enter libpcap,
+    /// use a `BreakHandle` to stop it from another thread.
+    ///
+    /// # Returns:
+    ///
+    /// - Number of packets given to the callback
+    ///
+    pub fn capture_loop<F>(&mut self, count: isize, callback: F) -> PResult<usize>
+    where
+        F: FnMut(LibPcapPacketInfo<'_>),
+    {
+        self.run_loop(count, callback, pcap_loop)
+    }
+
+    /// Process the packets of one read buffer with a callback, like `pcap_dispatch`
+    ///
+    /// Processes at most `count` packets, `-1` or `0` for the whole buffer, waits for the read timeout
+    /// unless in immediate or non-blocking mode.
+    ///
+    /// # Returns:
+    ///
+    /// - Number of packets given to the callback
+    ///
+    pub fn dispatch<F>(&mut self, count: isize, callback: F) -> PResult<usize>
+    where
+        F: FnMut(LibPcapPacketInfo<'_>),
+    {
+        self.run_loop(count, callback, pcap_dispatch)
+    }
+
+    fn run_loop<F>(
+        &mut self,
+        count: isize,
+        callback: F,
+        run: unsafe extern "C" fn(*mut pcap_t, i32, pcap_handler, *mut u_char) -> i32,
+    ) -> PResult<usize>
+    where
+        F: FnMut(LibPcapPacketInfo<'_>),
+    {
+        let mut state = LoopState {
+            handle: self.handle,
+            precision: TstampPrecision::from_value(unsafe { pcap_get_tstamp_precision(self.handle) } as u32),
+            callback,
+            count: 0,
+            panic: None,
+        };
+
+        let count = if count <= 0 { -1 } else { count.min(i32::MAX as isize) as i32 };
+        let ret = unsafe { run(self.handle, count, Some(packet_handler::<F>), &mut state as *mut LoopState<F> as *mut u_char) };
+
+        if let Some(e) = state.panic {
+            resume_unwind(e);
+        }
+
+        // `PCAP_ERROR_BREAK` after `breakloop` is a normal end
+        if ret == PCAP_ERROR_BREAK {
+            self.take_break();
+        }
+
+        if ret == PCAP_ERROR {
+            return Err(LibPcapError::CaptureError { msg: get_pcap_error(self.handle) });
+        }
+
+        Ok(state.count)
     }
 
     /// pcap stats
@@ -188,21 +907,17 @@ impl Sniff {
 }
 
 
-impl Drop for Sniff {
-    fn drop(&mut self) {
-        if !self.handle.is_null() {
-            unsafe { pcap_close(self.handle) };
-        }
-    }
-}
-
-
 /// Capture the packet iterator
+/// 
+/// Iterating yields owned `Packet` values, `SniffIterator::next_packet` borrows libpcap's buffer
+/// instead of copying it. A read timeout yields an empty packet stamped with the current time,
+/// `BreakHandle::breakloop` or a capture error ends the iteration, see `last_error`.
+#[derive(Debug)]
 pub struct SniffIterator<'a> {
-    handle: *mut pcap_t,
+    sniff: &'a mut Sniff,
     count: isize,
     index: isize,
-    _mark: PhantomData<&'a u8>,
+    error: Option<LibPcapError>,
 }
 
 
@@ -211,61 +926,66 @@ impl<'a> SniffIterator<'a> {
     /// 
     /// # Args:
     /// 
-    /// - `handle`: `pcap_create` handle.
-    /// - `count`: Capture the packet count.
+    /// - `sniff`: capture device, borrowed as long as packets are read.
+    /// - `count`: Capture the packet count, `-1` for no limit.
     /// 
     /// # Returns:
     /// 
     /// - `SniffIterator`
     /// 
-    pub fn new(handle: *mut pcap_t, count: isize) -> Self {
+    pub fn new(sniff: &'a mut Sniff, count: isize) -> Self {
         Self {
-            handle,
+            sniff,
             count,
             index: 0,
-            _mark: PhantomData,
+            error: None,
         }
     }
-}
-
 
-impl<'a> Iterator for SniffIterator<'a> {
-    type Item = LibPcapPacketInfo<'a>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.handle.is_null() {
+    /// Read the next packet without copying it
+    /// 
+    /// The returned buffer points into libpcap's own buffer and is overwritten by the next read,
+    /// so it borrows the iterator until it is dropped. Use `into_owned` to keep it.
+    pub fn next_packet(&mut self) -> Option<LibPcapPacketInfo<'_>> {
+        if self.error.is_some() || (self.count != -1 && self.index >= self.count) {
             return None;
         }
 
-        if self.count != -1 {
-            if self.index >= self.count {
-                return None;
-            }
+        match self.sniff.read_next() {
+            Ok(NextPacket::Packet(pkt)) => {
+                self.index += 1;
+                Some(pkt)
+            },
+            Ok(NextPacket::NotReady) => {
+                let ts = now_duration();
+
+                Some(LibPcapPacketInfo {
+                    timestamp: ts.as_secs() as i64,
+                    ts,
+                    caplen: 0,
+                    len: 0,
+                    buf: &[],
+                })
+            },
+            Ok(NextPacket::Break) => None,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
         }
+    }
 
-        let pkt_header = std::mem::MaybeUninit::uninit();
-        let mut pkt_header = unsafe { pkt_header.assume_init() };
+    /// Error that stopped the capture, `None` if it ended after `count` packets or a `breakloop`
+    pub fn last_error(&self) -> Option<&LibPcapError> {
+        self.error.as_ref()
+    }
+}
 
-        let pkt = unsafe {
-            let pkt = pcap_next(self.handle, &mut pkt_header);
-            std::slice::from_raw_parts(pkt, pkt_header.caplen as usize)
-        };
 
-        if pkt.is_empty() {
-            Some(Self::Item {
-                timestamp: now_timestamp() as i64,
-                caplen: 0,
-                buf: &[],
-            })
-        }
-        else {
-            self.index += 1;
+impl<'a> Iterator for SniffIterator<'a> {
+    type Item = Packet;
 
-            Some(Self::Item {
-                timestamp: pkt_header.ts.tv_sec,
-                caplen: pkt_header.caplen,
-                buf: &pkt,
-            })
-        }
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().map(LibPcapPacketInfo::into_owned)
     }
 }
@@ -0,0 +1,143 @@
+//! Async packet stream of a `Sniff`, see the `async` feature.
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures_core::Stream;
+use tokio::io::unix::AsyncFd;
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::packet::Packet;
+use crate::sniff::{Sniff, NextPacket};
+
+
+/// Packets read in a row before giving the other tasks a chance to run
+const BUDGET: usize = 64;
+
+
+/// Selectable descriptor of the capture device, owned and closed by libpcap
+struct SelectableFd(RawFd);
+
+
+impl AsRawFd for SelectableFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+
+/// Async `Stream` of captured packets, registered with the tokio reactor
+///
+/// The device is put in non-blocking mode and packets are only read when the stream is polled,
+/// a slow consumer leaves them in the kernel buffer, where they are eventually dropped and counted
+/// in `Sniff::stats`. Dropping the stream deregisters the descriptor and closes the device,
+/// unless a `BreakHandle` of it is still alive, the device is then closed with the last one.
+///
+/// `BreakHandle::breakloop` wakes the stream, which ends even on an idle device.
+///
+/// Must be created from within a tokio runtime.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::pin::Pin;
+/// use futures_core::Stream;
+/// use libpcap_rs::Sniff;
+///
+/// # async fn run() -> libpcap_rs::PResult<()> {
+/// let sniff = Sniff::open("eth0")?;
+/// sniff.with_filter("tcp port 80")?;
+///
+/// let mut stream = sniff.into_stream()?;
+///
+/// // Or `StreamExt::next` of futures or tokio-stream
+/// while let Some(pkt) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+///     println!("{:?}", pkt?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct SniffStream {
+    // Deregistered before the device is closed
+    fd: AsyncFd<SelectableFd>,
+    sniff: Sniff,
+    // Set once the capture was broken, the stream is over
+    done: bool,
+}
+
+
+impl SniffStream {
+    pub fn new(sniff: Sniff) -> PResult<Self> {
+        sniff.with_nonblock(true)?;
+
+        let fd = sniff.selectable_fd().ok_or_else(|| LibPcapError::CaptureError {
+            msg: format!("{} has no selectable file descriptor", sniff.iface),
+        })?;
+
+        Ok(Self { fd: AsyncFd::new(SelectableFd(fd))?, sniff, done: false })
+    }
+
+    pub fn get_ref(&self) -> &Sniff {
+        &self.sniff
+    }
+
+    /// Give back the device, still in non-blocking mode
+    pub fn into_inner(self) -> Sniff {
+        self.sniff
+    }
+}
+
+
+impl Stream for SniffStream {
+    type Item = PResult<Packet>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        // Registered before looking for a break, a later `breakloop` polls the stream again
+        this.sniff.register_waker(cx.waker());
+
+        for _ in 0..BUDGET {
+            if this.sniff.take_break() {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+
+            // libpcap may hold packets already read from the descriptor, try them first
+            match this.sniff.read_next() {
+                Ok(NextPacket::Packet(pkt)) => return Poll::Ready(Some(Ok(pkt.into_owned()))),
+                Ok(NextPacket::NotReady) => {},
+                Ok(NextPacket::Break) => {
+                    // libpcap clears the break flag once reported
+                    this.done = true;
+                    return Poll::Ready(None);
+                },
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            let mut guard = match this.fd.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            guard.clear_ready();
+        }
+
+        // Spurious readiness over and over, let the other tasks run
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+
+impl Sniff {
+    /// Turn the device into an async packet stream, see `SniffStream`
+    pub fn into_stream(self) -> PResult<SniffStream> {
+        SniffStream::new(self)
+    }
+}
@@ -0,0 +1,277 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::{LibPcap, PResult, Layer};
+use crate::net::vlan::{vlan_id, untagged};
+use crate::datalink::DataLink;
+use crate::packet::{LibPcapPacketInfo, TstampPrecision};
+use crate::path::insert_before_extension;
+use crate::time::strftime_utc;
+
+
+/// Number of output files kept open by default
+pub const DEFAULT_MAX_OPEN: usize = 64;
+/// Key of the packets a split criterion can't tell apart, e.g. non-IP packets when splitting by flow
+pub const OTHER_KEY: &str = "other";
+
+
+/// How packets are dispatched to the output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// A new file every N packets, like `editcap -c`
+    Packets(u64),
+    /// A new file every time window, starting at the first packet, like `editcap -i`
+    Interval(Duration),
+    /// One file per bidirectional 5-tuple, like SplitCap `-s session`
+    Flow,
+    /// One file per IP address, a packet goes to the files of both its hosts, like SplitCap `-s host`
+    Host,
+    /// One file per 802.1Q VLAN id, untagged and non Ethernet packets go to `OTHER_KEY`
+    Vlan,
+}
+
+
+/// Split a capture into several pcap files
+///
+/// Output names come from a template, `{index}` is replaced with the number of the packet count or
+/// time window and `{key}` with the flow, host or VLAN id. Without placeholder, the index or key is
+/// inserted before the extension: `split.pcap` gives `split.0.pcap`, `split.10.0.0.1.pcap`, ...
+/// strftime-style specifiers (`%Y %m %d %H %M %S %s`, UTC) are replaced with the timestamp of the
+/// first packet of the file.
+///
+/// At most `with_max_open` files are open at once, the least recently written one is closed
+/// and later reopened for appending when needed.
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::LibPcap;
+/// use libpcap_rs::split::{SplitBy, Splitter};
+///
+/// let mut input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+/// let mut splitter = Splitter::new(SplitBy::Flow, "split-{key}.pcap", input.linktype(), input.snaplen());
+///
+/// let mut iter = input.read();
+///
+/// while let Some(pkt) = iter.next_packet() {
+///     splitter.write_packet(&pkt).unwrap();
+/// }
+///
+/// splitter.close().unwrap();
+/// # for path in splitter.files() { std::fs::remove_file(path).unwrap(); }
+/// ```
+///
+pub struct Splitter {
+    by: SplitBy,
+    template: String,
+    linktype: i32,
+    snaplen: i32,
+    precision: TstampPrecision,
+    max_open: usize,
+    /// Output path of every key seen
+    paths: HashMap<String, PathBuf>,
+    /// Open outputs by key, with the tick of their last write
+    open: HashMap<String, (LibPcap, u64)>,
+    files: Vec<PathBuf>,
+    tick: u64,
+    packets: u64,
+    first_ts: Option<Duration>,
+}
+
+
+impl Splitter {
+    /// # Args:
+    ///
+    /// - `by`: split criterion
+    /// - `template`: output path template
+    /// - `linktype`: `DLT_*` link type of the input and the outputs
+    /// - `snaplen`: snapshot length of the outputs
+    ///
+    pub fn new(by: SplitBy, template: &str, linktype: i32, snaplen: i32) -> Self {
+        let placeholder = match by {
+            SplitBy::Packets(_) | SplitBy::Interval(_) => "{index}",
+            _ => "{key}",
+        };
+
+        let template = if template.contains(placeholder) {
+            template.to_string()
+        }
+        else {
+            insert_before_extension(template, placeholder)
+        };
+
+        Self {
+            by,
+            template,
+            linktype,
+            snaplen,
+            precision: TstampPrecision::Micro,
+            max_open: DEFAULT_MAX_OPEN,
+            paths: HashMap::new(),
+            open: HashMap::new(),
+            files: vec![],
+            tick: 0,
+            packets: 0,
+            first_ts: None,
+        }
+    }
+
+    pub fn with_tstamp_precision(mut self, precision: TstampPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Limit the number of simultaneously open output files, at least 1
+    pub fn with_max_open(mut self, value: usize) -> Self {
+        self.max_open = value.max(1);
+        self
+    }
+
+    /// Files written so far, in creation order
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Write a packet to the file(s) of its key
+    pub fn write_packet(&mut self, pkt: &LibPcapPacketInfo<'_>) -> PResult<()> {
+        let first_ts = *self.first_ts.get_or_insert(pkt.ts);
+
+        let keys = match self.by {
+            SplitBy::Packets(n) => vec![(self.packets / n.max(1)).to_string()],
+            SplitBy::Interval(interval) => {
+                let offset = pkt.ts.saturating_sub(first_ts).as_nanos();
+                vec![(offset / interval.as_nanos().max(1)).to_string()]
+            },
+            SplitBy::Flow => vec![self.flow_key(pkt.buf).unwrap_or_else(|| OTHER_KEY.to_string())],
+            SplitBy::Host => self.host_keys(pkt.buf).unwrap_or_else(|| vec![OTHER_KEY.to_string()]),
+            SplitBy::Vlan => vec![self.vlan_key(pkt.buf).unwrap_or_else(|| OTHER_KEY.to_string())],
+        };
+
+        self.packets += 1;
+
+        for key in keys {
+            self.tick += 1;
+
+            if !self.open.contains_key(&key) {
+                self.open_output(&key, pkt.ts)?;
+            }
+
+            if let Some((output, tick)) = self.open.get_mut(&key) {
+                output.write_packet(pkt)?;
+                *tick = self.tick;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close every output file
+    pub fn close(&mut self) -> PResult<()> {
+        let mut ret = Ok(());
+
+        for (_, (mut output, _)) in self.open.drain() {
+            let value = output.close();
+
+            if ret.is_ok() {
+                ret = value;
+            }
+        }
+
+        ret
+    }
+
+    fn open_output(&mut self, key: &str, ts: Duration) -> PResult<()> {
+        // Packet count and time splits write one file after another
+        if matches!(self.by, SplitBy::Packets(_) | SplitBy::Interval(_)) {
+            self.close()?;
+        }
+
+        // Close the least recently written output to stay under the limit
+        while self.open.len() >= self.max_open {
+            let Some(oldest) = self.open.iter().min_by_key(|(_, (_, tick))| *tick).map(|(k, _)| k.clone()) else {
+                break;
+            };
+
+            if let Some((mut output, _)) = self.open.remove(&oldest) {
+                output.close()?;
+            }
+        }
+
+        let (path, mode) = match self.paths.get(key) {
+            Some(path) => (path.clone(), "a"),
+            None => {
+                let name = strftime_utc(&self.template, ts)
+                    .replace("{index}", key)
+                    .replace("{key}", key);
+
+                (PathBuf::from(name), "w")
+            },
+        };
+
+        let output = LibPcap::open_with_header(&path.to_string_lossy(), mode, self.linktype, self.snaplen, self.precision)?;
+
+        if mode == "w" {
+            self.paths.insert(key.to_string(), path.clone());
+            self.files.push(path);
+        }
+
+        self.open.insert(key.to_string(), (output, self.tick));
+
+        Ok(())
+    }
+
+    fn layer<'a>(&self, buf: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        (DataLink::from(self.linktype) == DataLink::En10mb).then(|| untagged(buf))
+    }
+
+    fn vlan_key(&self, buf: &[u8]) -> Option<String> {
+        // The tag sits after the Ethernet addresses, other link types have something else there
+        if DataLink::from(self.linktype) != DataLink::En10mb {
+            return None;
+        }
+
+        vlan_id(buf).map(|v| v.to_string())
+    }
+
+    fn flow_key(&self, buf: &[u8]) -> Option<String> {
+        let buf = self.layer(buf)?;
+        let (_, layer) = jppe::decode_borrow::<Layer<'_>>(&buf).ok()?;
+
+        let protocol = layer.layer3.get_protocol()?;
+        let mut endpoints = [
+            (layer.layer3.get_src()?, layer.layer4.get_sport().unwrap_or_default()),
+            (layer.layer3.get_dst()?, layer.layer4.get_dport().unwrap_or_default()),
+        ];
+
+        // Both directions of a flow go to the same file
+        endpoints.sort();
+
+        let [(a, a_port), (b, b_port)] = endpoints;
+
+        Some(format!("{protocol}_{a}_{a_port}_{b}_{b_port}"))
+    }
+
+    fn host_keys(&self, buf: &[u8]) -> Option<Vec<String>> {
+        let buf = self.layer(buf)?;
+        let (_, layer) = jppe::decode_borrow::<Layer<'_>>(&buf).ok()?;
+
+        let src = layer.layer3.get_src()?;
+        let dst = layer.layer3.get_dst()?;
+
+        if src == dst {
+            Some(vec![src.to_string()])
+        }
+        else {
+            Some(vec![src.to_string(), dst.to_string()])
+        }
+    }
+}
+
+
+impl Drop for Splitter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
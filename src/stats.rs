@@ -0,0 +1,519 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use jppe::prelude::MacAddress;
+use crate::Layer;
+use crate::net::{Layer3, Layer4};
+use crate::net::vlan::{ETHERNET_HEADER_LEN, is_vlan_type, untagged};
+use crate::datalink::DataLink;
+use crate::packet::LibPcapPacketInfo;
+
+
+/// Name of the root of the protocol hierarchy
+pub const FRAME_PROTOCOL: &str = "frame";
+/// Name of the payload nothing was recognized in
+pub const DATA_PROTOCOL: &str = "data";
+
+const IPV6_HEADER_LEN: usize = 40;
+
+
+/// Protocol name of an ethertype, like Wireshark's filter names
+pub fn ethertype_name(value: u16) -> Option<&'static str> {
+    match value {
+        0x0800 => Some("ip"),
+        0x0806 => Some("arp"),
+        0x86dd => Some("ipv6"),
+        0x8100 | 0x88a8 | 0x9100 => Some("vlan"),
+        0x8863 | 0x8864 => Some("pppoe"),
+        0x8847 | 0x8848 => Some("mpls"),
+        0x88cc => Some("lldp"),
+        _ => None,
+    }
+}
+
+
+/// Protocol name of an IP protocol number
+pub fn ip_protocol_name(value: u8) -> Option<&'static str> {
+    match value {
+        1 => Some("icmp"),
+        2 => Some("igmp"),
+        6 => Some("tcp"),
+        17 => Some("udp"),
+        47 => Some("gre"),
+        50 => Some("esp"),
+        58 => Some("icmpv6"),
+        89 => Some("ospf"),
+        132 => Some("sctp"),
+        _ => None,
+    }
+}
+
+
+/// Guess the application protocol from the well-known port of a TCP or UDP packet
+///
+/// The lowest known port wins, `None` when neither port is known.
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::stats::guess_application;
+///
+/// assert_eq!(guess_application(6, 51234, 80), Some("http"));
+/// assert_eq!(guess_application(17, 53, 40000), Some("dns"));
+/// assert_eq!(guess_application(6, 51234, 51235), None);
+/// ```
+///
+pub fn guess_application(protocol: u8, sport: u16, dport: u16) -> Option<&'static str> {
+    let name = |port: u16| match (protocol, port) {
+        (6, 20 | 21) => Some("ftp"),
+        (6, 22) => Some("ssh"),
+        (6, 23) => Some("telnet"),
+        (6, 25 | 587) => Some("smtp"),
+        (_, 53) => Some("dns"),
+        (17, 67 | 68) => Some("dhcp"),
+        (6, 80 | 8000 | 8080) => Some("http"),
+        (6, 110) => Some("pop"),
+        (17, 123) => Some("ntp"),
+        (6, 143) => Some("imap"),
+        (17, 161 | 162) => Some("snmp"),
+        (6, 179) => Some("bgp"),
+        (6, 389) => Some("ldap"),
+        (6, 443 | 8443) => Some("tls"),
+        (6, 445) => Some("smb"),
+        (17, 443) => Some("quic"),
+        (17, 514) => Some("syslog"),
+        (6, 502) => Some("modbus"),
+        (17, 1900) => Some("ssdp"),
+        (6, 3306) => Some("mysql"),
+        (6, 5432) => Some("pgsql"),
+        (17, 5353) => Some("mdns"),
+        (6, 6379) => Some("redis"),
+        _ => None,
+    };
+
+    let (low, high) = if sport <= dport { (sport, dport) } else { (dport, sport) };
+
+    name(low).or_else(|| name(high))
+}
+
+
+/// Node of a protocol hierarchy, like `tshark -z io,phs`
+///
+/// The packets and bytes of a node include the ones of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolNode {
+    pub name: String,
+    pub packets: u64,
+    /// Sum of the original lengths
+    pub bytes: u64,
+    /// Sub-protocols, in order of appearance
+    pub children: Vec<ProtocolNode>,
+}
+
+
+impl ProtocolNode {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), packets: 0, bytes: 0, children: vec![] }
+    }
+
+    pub fn child(&self, name: &str) -> Option<&ProtocolNode> {
+        self.children.iter().find(|v| v.name == name)
+    }
+
+    /// Node at the end of a path of protocol names, e.g. `["eth", "ip", "tcp"]`
+    pub fn get(&self, path: &[&str]) -> Option<&ProtocolNode> {
+        path.iter().try_fold(self, |node, name| node.child(name))
+    }
+
+    /// Count a packet in this node and along a path of sub-protocols
+    pub fn add(&mut self, path: &[&str], bytes: u64) {
+        self.packets += 1;
+        self.bytes += bytes;
+
+        if let Some((name, path)) = path.split_first() {
+            let index = match self.children.iter().position(|v| v.name == *name) {
+                Some(index) => index,
+                None => {
+                    self.children.push(ProtocolNode::new(name));
+                    self.children.len() - 1
+                },
+            };
+
+            self.children[index].add(path, bytes);
+        }
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let name = format!("{:indent$}{}", "", self.name, indent = depth * 2);
+
+        writeln!(f, "{name:<40} frames:{} bytes:{}", self.packets, self.bytes)?;
+
+        for child in &self.children {
+            child.write_tree(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+impl fmt::Display for ProtocolNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+
+/// Traffic between two endpoints, `a` is the source of the first packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversation<E> {
+    pub a: E,
+    pub b: E,
+    pub packets_ab: u64,
+    pub bytes_ab: u64,
+    pub packets_ba: u64,
+    pub bytes_ba: u64,
+    pub first_ts: Duration,
+    pub last_ts: Duration,
+}
+
+
+impl<E> Conversation<E> {
+    pub fn packets(&self) -> u64 {
+        self.packets_ab + self.packets_ba
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes_ab + self.bytes_ba
+    }
+
+    /// Time between the first and the last packet
+    pub fn duration(&self) -> Duration {
+        self.last_ts.saturating_sub(self.first_ts)
+    }
+}
+
+
+/// Conversations between endpoints of one layer, like `tshark -z conv,<layer>`
+#[derive(Debug, Clone)]
+pub struct ConversationTable<E> {
+    conversations: Vec<Conversation<E>>,
+    /// Index of the conversations by `(a, b)`
+    index: HashMap<(E, E), usize>,
+    first_ts: Option<Duration>,
+}
+
+
+impl<E> Default for ConversationTable<E> {
+    fn default() -> Self {
+        Self { conversations: vec![], index: HashMap::new(), first_ts: None }
+    }
+}
+
+
+impl<E> ConversationTable<E> {
+    /// Take a packet into account for the start of the capture, it may belong to no conversation
+    fn observe(&mut self, ts: Duration) {
+        self.first_ts = Some(self.first_ts.map(|v| v.min(ts)).unwrap_or(ts));
+    }
+}
+
+
+impl<E: Hash + Eq + Clone> ConversationTable<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Conversations in order of appearance
+    pub fn conversations(&self) -> &[Conversation<E>] {
+        &self.conversations
+    }
+
+    pub fn len(&self) -> usize {
+        self.conversations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conversations.is_empty()
+    }
+
+    /// Conversation between two endpoints, in either direction
+    pub fn get(&self, a: &E, b: &E) -> Option<&Conversation<E>> {
+        let key = (a.clone(), b.clone());
+        let index = self.index.get(&key).or_else(|| self.index.get(&(key.1, key.0)))?;
+
+        self.conversations.get(*index)
+    }
+
+    /// Count a packet from `src` to `dst`
+    pub fn add(&mut self, src: E, dst: E, ts: Duration, bytes: u64) {
+        self.observe(ts);
+
+        let key = (src, dst);
+
+        if let Some(index) = self.index.get(&key) {
+            let conv = &mut self.conversations[*index];
+
+            conv.packets_ab += 1;
+            conv.bytes_ab += bytes;
+            conv.last_ts = conv.last_ts.max(ts);
+            return;
+        }
+
+        let key = (key.1, key.0);
+
+        if let Some(index) = self.index.get(&key) {
+            let conv = &mut self.conversations[*index];
+
+            conv.packets_ba += 1;
+            conv.bytes_ba += bytes;
+            conv.last_ts = conv.last_ts.max(ts);
+            return;
+        }
+
+        let (dst, src) = key;
+
+        self.index.insert((src.clone(), dst.clone()), self.conversations.len());
+        self.conversations.push(Conversation {
+            a: src,
+            b: dst,
+            packets_ab: 1,
+            bytes_ab: bytes,
+            packets_ba: 0,
+            bytes_ba: 0,
+            first_ts: ts,
+            last_ts: ts,
+        });
+    }
+}
+
+
+/// Table sorted by bytes, the start time is relative to the first packet of the capture
+impl<E: ToString> fmt::Display for ConversationTable<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conversations: Vec<_> = self.conversations.iter().collect();
+        let first_ts = self.first_ts.unwrap_or_default();
+
+        conversations.sort_by_key(|v| Reverse(v.bytes()));
+
+        write!(f, "{:<24} {:<24} {:>10} {:>12} {:>10} {:>12} {:>10} {:>12} {:>12} {:>12}",
+            "Address A", "Address B", "Frames A→B", "Bytes A→B", "Frames B→A", "Bytes B→A",
+            "Frames", "Bytes", "Rel Start", "Duration",
+        )?;
+
+        for conv in conversations {
+            let start = conv.first_ts.saturating_sub(first_ts);
+
+            write!(f, "\n{:<24} {:<24} {:>10} {:>12} {:>10} {:>12} {:>10} {:>12} {:>12.6} {:>12.6}",
+                conv.a.to_string(), conv.b.to_string(), conv.packets_ab, conv.bytes_ab, conv.packets_ba, conv.bytes_ba,
+                conv.packets(), conv.bytes(), start.as_secs_f64(), conv.duration().as_secs_f64(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Protocol hierarchy and conversation statistics of a capture
+///
+/// Ethernet frames are decoded with `Layer`, VLAN tags are skipped. `Layer` has no IPv6, the
+/// addresses and ports of IPv6 packets are read from the raw header after its extension headers.
+/// Packets of other link types only count in the `frame` node. The application protocol is guessed from the well-known ports,
+/// see `guess_application`. Byte counts use the original length of the packets.
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::LibPcap;
+/// use libpcap_rs::stats::CaptureStats;
+///
+/// let mut input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+/// let mut stats = CaptureStats::new(input.datalink());
+///
+/// let mut iter = input.read();
+///
+/// while let Some(pkt) = iter.next_packet() {
+///     stats.add_packet(&pkt);
+/// }
+///
+/// assert_eq!(stats.hierarchy.get(&["eth", "ip", "tcp"]).unwrap().packets, 41);
+///
+/// println!("{}", stats.hierarchy);
+/// println!("{}", stats.tcp);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct CaptureStats {
+    datalink: DataLink,
+    pub hierarchy: ProtocolNode,
+    /// Conversations between MAC addresses
+    pub eth: ConversationTable<MacAddress>,
+    /// Conversations between IPv4 or IPv6 addresses
+    pub ip: ConversationTable<IpAddr>,
+    /// TCP conversations, by 5-tuple
+    pub tcp: ConversationTable<SocketAddr>,
+    /// UDP conversations, by 5-tuple
+    pub udp: ConversationTable<SocketAddr>,
+}
+
+
+impl CaptureStats {
+    pub fn new(datalink: DataLink) -> Self {
+        Self {
+            datalink,
+            hierarchy: ProtocolNode::new(FRAME_PROTOCOL),
+            eth: ConversationTable::new(),
+            ip: ConversationTable::new(),
+            tcp: ConversationTable::new(),
+            udp: ConversationTable::new(),
+        }
+    }
+
+    pub fn add_packet(&mut self, pkt: &LibPcapPacketInfo<'_>) {
+        let bytes = pkt.len as u64;
+
+        self.eth.observe(pkt.ts);
+        self.ip.observe(pkt.ts);
+        self.tcp.observe(pkt.ts);
+        self.udp.observe(pkt.ts);
+
+        if self.datalink != DataLink::En10mb || pkt.buf.len() < ETHERNET_HEADER_LEN {
+            self.hierarchy.add(&[], bytes);
+            return;
+        }
+
+        let mut path = vec!["eth"];
+
+        if is_vlan_type(u16::from_be_bytes([pkt.buf[12], pkt.buf[13]])) {
+            path.push("vlan");
+        }
+
+        let buf = untagged(pkt.buf);
+        let ethertype = u16::from_be_bytes([buf[12], buf[13]]);
+
+        self.eth.add(mac_address(&buf[6..12]), mac_address(&buf[..6]), pkt.ts, bytes);
+
+        if ethertype == 0x86dd {
+            self.add_ipv6(&mut path, &buf[ETHERNET_HEADER_LEN..], pkt.ts, bytes);
+        }
+        else {
+            match jppe::decode_borrow::<Layer<'_>>(&buf) {
+                Ok((_, layer)) if ethertype == 0x0800 => self.add_layer(&mut path, &layer, pkt.ts, bytes),
+                _ => path.push(ethertype_name(ethertype).unwrap_or(DATA_PROTOCOL)),
+            }
+        }
+
+        self.hierarchy.add(&path, bytes);
+    }
+
+    fn add_layer(&mut self, path: &mut Vec<&str>, layer: &Layer<'_>, ts: Duration, bytes: u64) {
+        let Layer3::Ipv4(ip) = &layer.layer3 else {
+            return;
+        };
+
+        path.push("ip");
+
+        self.ip.add(IpAddr::V4(ip.src), IpAddr::V4(ip.dst), ts, bytes);
+
+        // Only the first fragment holds the transport header
+        if ip.fragment_offset != 0 {
+            path.push(DATA_PROTOCOL);
+            return;
+        }
+
+        let (sport, dport) = match &layer.layer4 {
+            Layer4::Tcp(v) => (v.sport, v.dport),
+            Layer4::Udp(v) => (v.sport, v.dport),
+            Layer4::Unknown => {
+                path.push(ip_protocol_name(ip.protocol).unwrap_or(DATA_PROTOCOL));
+                return;
+            },
+        };
+
+        let src = SocketAddr::new(IpAddr::V4(ip.src), sport);
+        let dst = SocketAddr::new(IpAddr::V4(ip.dst), dport);
+
+        self.add_transport(path, ip.protocol, (src, dst), !layer.remain.is_empty(), ts, bytes);
+    }
+
+    fn add_ipv6(&mut self, path: &mut Vec<&str>, buf: &[u8], ts: Duration, bytes: u64) {
+        path.push("ipv6");
+
+        if buf.len() < IPV6_HEADER_LEN {
+            return;
+        }
+
+        // Without the Ethernet padding
+        let payload_len = u16::from_be_bytes([buf[4], buf[5]]) as usize;
+        let buf = &buf[..buf.len().min(IPV6_HEADER_LEN + payload_len)];
+
+        let src = IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&buf[8..24]).unwrap_or_default()));
+        let dst = IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&buf[24..40]).unwrap_or_default()));
+
+        self.ip.add(src, dst, ts, bytes);
+
+        let mut protocol = buf[6];
+        let mut offset = IPV6_HEADER_LEN;
+
+        // Hop-by-hop, routing, fragment and destination options headers come before the transport header
+        loop {
+            match protocol {
+                0 | 43 | 60 if buf.len() >= offset + 2 => {
+                    protocol = buf[offset];
+                    offset += (buf[offset + 1] as usize + 1) * 8;
+                },
+                44 if buf.len() >= offset + 8 => {
+                    // Only the first fragment holds the transport header
+                    if u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) >> 3 != 0 {
+                        path.push(DATA_PROTOCOL);
+                        return;
+                    }
+
+                    protocol = buf[offset];
+                    offset += 8;
+                },
+                _ => break,
+            }
+        }
+
+        let l4 = buf.get(offset..).unwrap_or_default();
+
+        let header_len = match protocol {
+            6 if l4.len() >= 20 => (l4[12] >> 4) as usize * 4,
+            17 if l4.len() >= 8 => 8,
+            _ => {
+                path.push(ip_protocol_name(protocol).unwrap_or(DATA_PROTOCOL));
+                return;
+            },
+        };
+
+        let src = SocketAddr::new(src, u16::from_be_bytes([l4[0], l4[1]]));
+        let dst = SocketAddr::new(dst, u16::from_be_bytes([l4[2], l4[3]]));
+
+        self.add_transport(path, protocol, (src, dst), l4.len() > header_len, ts, bytes);
+    }
+
+    /// Count a TCP or UDP packet, the application is guessed when it has a payload
+    fn add_transport(&mut self, path: &mut Vec<&str>, protocol: u8, (src, dst): (SocketAddr, SocketAddr), payload: bool, ts: Duration, bytes: u64) {
+        let (table, name) = if protocol == 6 { (&mut self.tcp, "tcp") } else { (&mut self.udp, "udp") };
+
+        path.push(name);
+        table.add(src, dst, ts, bytes);
+
+        if payload {
+            path.push(guess_application(protocol, src.port(), dst.port()).unwrap_or(DATA_PROTOCOL));
+        }
+    }
+}
+
+
+fn mac_address(buf: &[u8]) -> MacAddress {
+    let mut value = MacAddress::default();
+
+    value.copy_from_slice(buf);
+    value
+}
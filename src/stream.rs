@@ -0,0 +1,87 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use crate::PResult;
+use crate::errors::LibPcapError;
+use crate::compress::{Compression, CompressReader, CompressWriter};
+use crate::path::join_home;
+
+
+/// Path standing for stdin when reading and stdout when writing, as in libpcap
+pub const STDIO_PATH: &str = "-";
+
+
+/// Capture input from a file, stdin or any reader, decompressed when needed
+pub type InputStream = CompressReader<BufReader<Box<dyn Read + Send>>>;
+/// Capture output to a file, stdout or any writer, optionally compressed
+pub type OutputStream = CompressWriter<BufWriter<Box<dyn Write + Send>>>;
+
+
+/// Open a capture for reading, `-` is stdin
+///
+/// # Example:
+///
+/// ```rust
+/// use libpcap_rs::stream::open_input;
+/// use libpcap_rs::native::PcapReader;
+///
+/// let reader = PcapReader::new(open_input("tests/pcap/http_1.pcap").unwrap()).unwrap();
+///
+/// println!("{:?}", reader.header());
+/// ```
+///
+pub fn open_input(path: &str) -> PResult<InputStream> {
+    if path == STDIO_PATH {
+        return input_stream(std::io::stdin());
+    }
+
+    let pathobj = join_home(path);
+
+    if !pathobj.exists() {
+        return Err(LibPcapError::FileNotExtists { path: path.to_string() });
+    }
+
+    if !pathobj.is_file() {
+        return Err(LibPcapError::InvalidFile { path: path.to_string() });
+    }
+
+    input_stream(File::open(pathobj)?)
+}
+
+
+/// Wrap any reader, the compression is detected from the first bytes
+pub fn input_stream<R: Read + Send + 'static>(reader: R) -> PResult<InputStream> {
+    let reader: Box<dyn Read + Send> = Box::new(reader);
+
+    CompressReader::new(BufReader::new(reader))
+}
+
+
+/// Open a capture for writing, `-` is stdout
+///
+/// A file is compressed when its path ends with `.gz`, `.zst` or `.xz`,
+/// when appending the codec of the existing data is kept.
+pub fn create_output(path: &str, append: bool) -> PResult<OutputStream> {
+    if path == STDIO_PATH {
+        return output_stream(std::io::stdout(), Compression::None);
+    }
+
+    let pathobj = join_home(path);
+    let compression = Compression::for_output(&pathobj, append);
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(&pathobj)?;
+
+    output_stream(file, compression)
+}
+
+
+/// Wrap any writer
+pub fn output_stream<W: Write + Send + 'static>(writer: W, compression: Compression) -> PResult<OutputStream> {
+    let writer: Box<dyn Write + Send> = Box::new(writer);
+
+    CompressWriter::new(BufWriter::new(writer), compression)
+}
@@ -1,7 +1,81 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 
 #[inline]
-pub fn now_timestamp() -> u64 {
-    SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs()
+pub fn now_duration() -> Duration {
+    SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default()
+}
+
+
+/// Convert days since the UNIX epoch to a `(year, month, day)` civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = (z - era * 146_097) as u64;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe as i64 + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+
+/// Whether `pattern` holds a `strftime_utc` time specifier
+pub fn has_time_specifier(pattern: &str) -> bool {
+    ["%Y", "%m", "%d", "%H", "%M", "%S", "%s"].iter().any(|v| pattern.contains(v))
+}
+
+
+/// Format a timestamp with a subset of strftime, in UTC
+/// 
+/// Supports `%Y %m %d %H %M %S %s %%`, other specifiers are kept as is.
+pub fn strftime_utc(pattern: &str, ts: Duration) -> String {
+    let secs = ts.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let tod = secs.rem_euclid(86_400);
+    let mut value = String::with_capacity(pattern.len() + 16);
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            value.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('Y') => value.push_str(&format!("{year:04}")),
+            Some('m') => value.push_str(&format!("{month:02}")),
+            Some('d') => value.push_str(&format!("{day:02}")),
+            Some('H') => value.push_str(&format!("{:02}", tod / 3_600)),
+            Some('M') => value.push_str(&format!("{:02}", tod % 3_600 / 60)),
+            Some('S') => value.push_str(&format!("{:02}", tod % 60)),
+            Some('s') => value.push_str(&secs.to_string()),
+            Some('%') => value.push('%'),
+            Some(c) => {
+                value.push('%');
+                value.push(c);
+            },
+            None => value.push('%'),
+        }
+    }
+
+    value
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strftime_utc() {
+        let ts = Duration::new(1084443427, 311224000);
+
+        assert_eq!(strftime_utc("cap-%Y%m%d-%H%M%S.pcap", ts), "cap-20040513-101707.pcap");
+        assert_eq!(strftime_utc("%s %% %q", ts), "1084443427 % %q");
+        assert_eq!(strftime_utc("%Y-%m-%d", Duration::from_secs(951782400)), "2000-02-29");
+    }
 }
@@ -1,56 +1,56 @@
-use std::marker::PhantomData;
 use std::ptr::null_mut;
+use std::io::{Read, Write};
+use std::thread::JoinHandle;
 use std::str::FromStr;
 use std::ffi::{CString, CStr};
-use std::path::{Path, PathBuf};
-use jkcenum::JkcEnum;
+use std::time::Duration;
 use crate::PResult;
 use crate::errors::LibPcapError;
-use crate::time::now_timestamp;
+use crate::time::now_duration;
 use crate::make_cstr;
+use crate::compress::Compression;
+use crate::stream::{InputStream, OutputStream, STDIO_PATH, open_input, create_output, input_stream, output_stream};
+use crate::native::PcapReader;
+use crate::interface::get_interfaces;
+use crate::sniff::Sniff;
 use crate::libpcap::{
-    pcap_t, pcap_pkthdr, pcap_dumper_t, bpf_program,
-    PCAP_ERRBUF_SIZE,
-    pcap_open_offline, pcap_dump_open, pcap_open_dead, pcap_dump_open_append,
-    pcap_next, pcap_close, pcap_dump, pcap_dump_close, pcap_dump_flush, pcap_compile, pcap_setfilter, pcap_freecode,
-    pcap_lookupdev, pcap_findalldevs, pcap_freealldevs, pcap_open_live, pcap_sendpacket,
+    pcap_t, pcap_pkthdr, pcap_dumper_t, bpf_program, timeval, FILE,
+    PCAP_ERRBUF_SIZE, PCAP_ERROR_BREAK,
+    pcap_open_offline_with_tstamp_precision, pcap_fopen_offline_with_tstamp_precision, pcap_dump_open, pcap_dump_fopen, fdopen, fclose, pcap_open_dead_with_tstamp_precision, pcap_dump_open_append,
+    pcap_next_ex, pcap_geterr, pcap_close, pcap_datalink, pcap_snapshot,
+    pcap_dump, pcap_dump_close, pcap_dump_flush, pcap_dump_ftell, pcap_dump_file, fileno, ferror, pcap_compile, pcap_setfilter, pcap_freecode,
 };
+pub use crate::packet::{LibPcapMode, LibPcapPacketInfo, Packet, TstampPrecision};
+pub use crate::datalink::{DataLink, DEFAULT_LINKTYPE, DEFAULT_SNAPLEN};
+pub use crate::path::join_home;
 
 
+#[derive(Debug)]
 pub struct LibPcap {
     in_pcap: *mut pcap_t,
     out_pcap: *mut pcap_dumper_t,
+    /// `pcap_open_dead` handle the dumper was opened with, owned and closed after it
+    dead_pcap: *mut pcap_t,
+    precision: TstampPrecision,
+    linktype: i32,
+    snaplen: i32,
+    /// Thread (de)compressing between libpcap and a compressed file
+    pump: Option<JoinHandle<PResult<()>>>,
 }
 
 
-#[derive(Debug, Default, JkcEnum, PartialEq, Eq)]
-pub enum LibPcapMode {
-    #[default]
-    #[jenum(rename="r")]
-    Read,
-    #[jenum(rename="w")]
-    Write,
-    #[jenum(rename="a")]
-    Append,
-}
-
-
-#[derive(Debug)]
-pub struct LibPcapPacketInfo<'a> {
-    pub timestamp: i64,
-    pub caplen: u32,
-    pub buf: &'a [u8],
+/// Convert a `pcap_pkthdr.ts` to the duration since the UNIX epoch
+pub(crate) fn timeval_to_duration(ts: &timeval, precision: TstampPrecision) -> Duration {
+    precision.to_duration(ts.tv_sec as u64, ts.tv_usec as u32)
 }
 
 
-pub fn join_home<'a>(path: &'a str) -> PathBuf {
-    if let Some(path) = path.strip_prefix('~') {
-        if let Ok(home_dir) = std::env::var("HOME") {
-            return Path::new(&home_dir).join(Path::new(path));
-        }
+/// Convert a duration since the UNIX epoch to a `pcap_pkthdr.ts`
+pub(crate) fn duration_to_timeval(ts: Duration, precision: TstampPrecision) -> timeval {
+    timeval {
+        tv_sec: ts.as_secs() as _,
+        tv_usec: precision.subsec(ts) as _,
     }
-
-    Path::new(path).to_path_buf()
 }
 
 
@@ -92,24 +92,213 @@ pub fn libpcap_set_filter<T: Into<Vec<u8>>>(handle: *mut pcap_t, bpf_filter: T)
 
 impl Drop for LibPcap {
     fn drop(&mut self) {
-        if !self.out_pcap.is_null() {
-            unsafe {
-                pcap_dump_flush(self.out_pcap);
-                pcap_dump_close(self.out_pcap);
-            }
-        }
+        let _ = self.close();
+    }
+}
 
-        if !self.in_pcap.is_null() {
-            unsafe {
-                pcap_close(self.in_pcap);
-            }
+
+/// Copy a stream into a stdio stream in a thread, for libpcap to read it with `pcap_fopen_offline`
+#[cfg(unix)]
+fn open_read_pump(mut reader: InputStream) -> PResult<(*mut FILE, JoinHandle<PResult<()>>)> {
+    use std::os::unix::net::UnixStream;
+
+    let (rx, mut tx) = UnixStream::pair()?;
+    let fp = fdopen_socket(rx, b"r\0")?;
+
+    let pump = std::thread::spawn(move || {
+        std::io::copy(&mut reader, &mut tx)?;
+        Ok(())
+    });
+
+    Ok((fp, pump))
+}
+
+
+/// Copy what libpcap writes to the returned stdio stream into `writer` in a thread
+/// 
+/// When appending, the file header libpcap writes is dropped as the file already has one.
+#[cfg(unix)]
+fn open_write_pump(mut writer: OutputStream, append: bool) -> PResult<(*mut FILE, JoinHandle<PResult<()>>)> {
+    use std::os::unix::net::UnixStream;
+
+    let (mut rx, tx) = UnixStream::pair()?;
+    let fp = fdopen_socket(tx, b"w\0")?;
+
+    let pump = std::thread::spawn(move || {
+        if append {
+            let mut header = [0; crate::native::pcap::PCAP_HEADER_LEN];
+            rx.read_exact(&mut header)?;
         }
+
+        std::io::copy(&mut rx, &mut writer)?;
+        writer.try_finish()?;
+
+        Ok(())
+    });
+
+    Ok((fp, pump))
+}
+
+
+/// Hand one end of a socket pair to a stdio stream, closing it if `fdopen` fails
+#[cfg(unix)]
+fn fdopen_socket(socket: std::os::unix::net::UnixStream, mode: &[u8]) -> PResult<*mut FILE> {
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+    let fd = socket.into_raw_fd();
+    let fp = unsafe { fdopen(fd, mode.as_ptr() as _) };
+
+    if fp.is_null() {
+        let e = std::io::Error::last_os_error();
+        drop(unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) });
+        return Err(e.into());
     }
+
+    Ok(fp)
+}
+
+
+#[cfg(not(unix))]
+fn open_read_pump(_reader: InputStream) -> PResult<(*mut FILE, JoinHandle<PResult<()>>)> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+}
+
+
+#[cfg(not(unix))]
+fn open_write_pump(_writer: OutputStream, _append: bool) -> PResult<(*mut FILE, JoinHandle<PResult<()>>)> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
 }
 
 
 impl<'a> LibPcap {
     pub fn open(path: &'a str, mode: &'a str) -> Result<Self, LibPcapError> {
+        Self::open_with_tstamp_precision(path, mode, TstampPrecision::Micro)
+    }
+
+    /// Open a pcap file with the given timestamp precision
+    /// 
+    /// Reading a microsecond file with `TstampPrecision::Nano` scales its timestamps,
+    /// writing with `TstampPrecision::Nano` produces a nanosecond pcap file.
+    /// 
+    /// # Args:
+    /// 
+    /// - `path`: pcap file path
+    /// - `mode`: `r`, `w` or `a`
+    /// - `precision`: timestamp precision
+    /// 
+    pub fn open_with_tstamp_precision(path: &'a str, mode: &'a str, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, DEFAULT_LINKTYPE, DEFAULT_SNAPLEN, precision)
+    }
+
+    /// Open a pcap file for writing with the given link type and snaplen
+    /// 
+    /// In read mode the link type and snaplen are taken from the file itself.
+    /// 
+    /// # Args:
+    /// 
+    /// - `path`: pcap file path
+    /// - `mode`: `w` or `a`
+    /// - `linktype`: `DLT_*` link type written in the file header
+    /// - `snaplen`: snapshot length written in the file header
+    /// 
+    pub fn open_with_linktype(path: &'a str, mode: &'a str, linktype: i32, snaplen: i32) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, linktype, snaplen, TstampPrecision::Micro)
+    }
+
+    /// Open a pcap file for writing with the given link type, snaplen and timestamp precision
+    pub fn open_with_header(path: &'a str, mode: &'a str, linktype: i32, snaplen: i32, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, linktype, snaplen, precision)
+    }
+
+    /// Open a pcap file for writing with the link type, snaplen and timestamp precision of another pcap file
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    /// let output = LibPcap::open_like("test_like.pcap", "w", &input).unwrap();
+    /// 
+    /// assert_eq!(input.linktype(), output.linktype());
+    /// ```
+    /// 
+    pub fn open_like(path: &'a str, mode: &'a str, other: &LibPcap) -> Result<Self, LibPcapError> {
+        Self::open_inner(path, mode, other.linktype, other.snaplen, other.precision)
+    }
+
+    /// Read a pcap or pcapng capture from any reader, e.g. stdin, a pipe or an in-memory buffer
+    /// 
+    /// The stream is handed to `pcap_fopen_offline` through a socket pair fed by a thread.
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// use libpcap_rs::packet::TstampPrecision;
+    /// 
+    /// let buf = std::fs::read("tests/pcap/http_1.pcap").unwrap();
+    /// let mut f = LibPcap::from_reader(std::io::Cursor::new(buf), TstampPrecision::Micro).unwrap();
+    /// 
+    /// assert!(f.read().count() > 0);
+    /// ```
+    /// 
+    pub fn from_reader<R: Read + Send + 'static>(reader: R, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        Self::with_input(input_stream(reader)?, precision)
+    }
+
+    fn with_input(input: InputStream, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
+        let (fp, pump) = open_read_pump(input)?;
+
+        let in_pcap = unsafe { pcap_fopen_offline_with_tstamp_precision(fp, precision.value(), errbuf.as_mut_ptr()) };
+
+        if in_pcap.is_null() {
+            unsafe { fclose(fp) };
+            return Err(LibPcapError::InvalidHeader { msg: make_cstr!(errbuf.as_ptr()) });
+        }
+
+        Ok(Self {
+            in_pcap,
+            out_pcap: null_mut(),
+            dead_pcap: null_mut(),
+            precision,
+            linktype: unsafe { pcap_datalink(in_pcap) },
+            snaplen: unsafe { pcap_snapshot(in_pcap) },
+            pump: Some(pump),
+        })
+    }
+
+    /// Write a pcap capture to any writer, e.g. stdout or a socket
+    pub fn from_writer<W: Write + Send + 'static>(writer: W, linktype: i32, snaplen: i32, precision: TstampPrecision) -> Result<Self, LibPcapError> {
+        let mut pcap = Self::dead(linktype, snaplen, precision);
+        let (fp, pump) = open_write_pump(output_stream(writer, Compression::None)?, false)?;
+
+        pcap.pump = Some(pump);
+        pcap.out_pcap = unsafe { pcap_dump_fopen(pcap.dead_pcap, fp) };
+
+        if pcap.out_pcap.is_null() {
+            unsafe { fclose(fp) };
+            return Err(LibPcapError::OpenModeError);
+        }
+
+        Ok(pcap)
+    }
+
+    /// Handle with a `pcap_open_dead` pcap_t to open a dumper with, closed when dropped
+    fn dead(linktype: i32, snaplen: i32, precision: TstampPrecision) -> Self {
+        Self {
+            in_pcap: null_mut(),
+            out_pcap: null_mut(),
+            dead_pcap: unsafe { pcap_open_dead_with_tstamp_precision(linktype, snaplen, precision.value()) },
+            precision,
+            linktype,
+            snaplen,
+            pump: None,
+        }
+    }
+
+    fn open_inner(path: &'a str, mode: &'a str, linktype: i32, snaplen: i32, precision: TstampPrecision) -> Result<Self, LibPcapError> {
         let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
         let pathobj = join_home(path);
         let mut mode_tmp = LibPcapMode::Read;
@@ -118,6 +307,11 @@ impl<'a> LibPcap {
             mode_tmp = v;
         }
 
+        // stdin may be compressed, it goes through the same pump as any other reader
+        if mode_tmp == LibPcapMode::Read && (path == STDIO_PATH || Compression::detect_file(&pathobj) != Compression::None) {
+            return Self::with_input(open_input(path)?, precision);
+        }
+
         if mode_tmp == LibPcapMode::Read && !pathobj.exists() {
             return Err(LibPcapError::FileNotExtists { path: path.to_string() });
         }
@@ -128,42 +322,185 @@ impl<'a> LibPcap {
 
         let path_cstring = CString::new(pathobj.to_str().unwrap_or_default()).unwrap_or_default();
 
-        let in_pcap = if mode_tmp == LibPcapMode::Read { 
-            let value = unsafe { pcap_open_offline(path_cstring.as_ptr(), errbuf.as_mut_ptr()) };
+        if mode_tmp == LibPcapMode::Read {
+            let in_pcap = unsafe { pcap_open_offline_with_tstamp_precision(path_cstring.as_ptr(), precision.value(), errbuf.as_mut_ptr()) };
+
+            if in_pcap.is_null() {
+                return Err(LibPcapError::InvalidHeader { msg: make_cstr!(errbuf.as_ptr()) })
+            }
+
+            return Ok(Self {
+                in_pcap,
+                out_pcap: null_mut(),
+                dead_pcap: null_mut(),
+                precision,
+                linktype: unsafe { pcap_datalink(in_pcap) },
+                snaplen: unsafe { pcap_snapshot(in_pcap) },
+                pump: None,
+            });
+        }
+
+        let append = mode_tmp == LibPcapMode::Append;
+        let compression = Compression::for_output(&pathobj, append);
+        let mut pcap = Self::dead(linktype, snaplen, precision);
+
+        pcap.out_pcap = if compression != Compression::None {
+            let append = append && pathobj.metadata().map(|v| v.len() > 0).unwrap_or_default();
+
+            if append {
+                let header = PcapReader::new(open_input(path)?).map_err(|e| match e {
+                    LibPcapError::InvalidHeader { .. } => LibPcapError::InvalidFile { path: path.to_string() },
+                    e => e,
+                })?.header().clone();
+
+                if header.datalink().value() != linktype || header.precision != precision {
+                    return Err(LibPcapError::InvalidFile { path: path.to_string() });
+                }
+            }
+
+            let (fp, handle) = open_write_pump(create_output(path, append)?, append)?;
+            pcap.pump = Some(handle);
+
+            let value = unsafe { pcap_dump_fopen(pcap.dead_pcap, fp) };
 
             if value.is_null() {
-                return Err(LibPcapError::InvalidFile { path: path.to_string() })
-            }    
+                unsafe { fclose(fp) };
+            }
 
             value
-        } else { null_mut() };
+        }
+        else if append {
+            unsafe { pcap_dump_open_append(pcap.dead_pcap, path_cstring.as_ptr()) }
+        }
+        else {
+            unsafe { pcap_dump_open(pcap.dead_pcap, path_cstring.as_ptr()) }
+        };
 
-        let out_pcap = match mode_tmp {
-            LibPcapMode::Write => {
-                let value = unsafe { pcap_dump_open(pcap_open_dead (1, 65535), path_cstring.as_ptr()) };
+        if pcap.out_pcap.is_null() {
+            return Err(LibPcapError::InvalidFile { path: path.to_string() });
+        }
 
-                if value.is_null() {
-                    return Err(LibPcapError::InvalidFile { path: path.to_string() })
-                }    
+        Ok(pcap)
+    }
 
-                value
-            },
-            LibPcapMode::Append => {
-                let value = unsafe { pcap_dump_open_append(pcap_open_dead (1, 65535), path_cstring.as_ptr()) };
+    /// `DLT_*` link type of the opened file
+    pub fn linktype(&self) -> i32 {
+        self.linktype
+    }
 
-                if value.is_null() {
-                    return Err(LibPcapError::InvalidFile { path: path.to_string() })
-                }    
+    /// Link type of the opened file
+    pub fn datalink(&self) -> DataLink {
+        DataLink::from(self.linktype)
+    }
 
-                value
-            },
-            _ => null_mut(),
-        };
+    /// Snapshot length of the opened file
+    pub fn snaplen(&self) -> i32 {
+        self.snaplen
+    }
 
-        Ok(Self {
-            in_pcap,
-            out_pcap,
-        })
+    /// Timestamp precision of the opened file
+    pub fn tstamp_precision(&self) -> TstampPrecision {
+        self.precision
+    }
+
+    /// Current size of the output file from `pcap_dump_ftell`, buffered bytes included
+    /// 
+    /// `None` when not writing, or when the output is compressed or a stream and can't be told.
+    pub fn position(&self) -> Option<u64> {
+        if self.out_pcap.is_null() {
+            return None;
+        }
+
+        let value = unsafe { pcap_dump_ftell(self.out_pcap) };
+
+        if value < 0 { None } else { Some(value as u64) }
+    }
+
+    /// Write the buffered packets to the output with `pcap_dump_flush`
+    pub fn flush(&self) -> PResult<()> {
+        if !self.out_pcap.is_null() && unsafe { pcap_dump_flush(self.out_pcap) } == -1 {
+            return Err(LibPcapError::FlushError { msg: std::io::Error::last_os_error().to_string() });
+        }
+
+        Ok(())
+    }
+
+    /// Flush the output and ask the OS to write it to disk
+    /// 
+    /// Outputs that are not files, e.g. compressed files, pipes or stdout, are only flushed.
+    pub fn sync(&self) -> PResult<()> {
+        self.flush()?;
+
+        #[cfg(unix)]
+        if !self.out_pcap.is_null() && self.pump.is_none() {
+            use std::os::unix::io::FromRawFd;
+
+            let fd = unsafe { fileno(pcap_dump_file(self.out_pcap)) };
+            // The descriptor is owned by the dumper's stdio stream
+            let file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+
+            match file.sync_all() {
+                Err(e) if e.kind() != std::io::ErrorKind::InvalidInput => return Err(e.into()),
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush and close the output and the input, further writes fail with `OpenModeError`
+    /// 
+    /// Dropping a `LibPcap` closes it too but can't report errors, a writer should be closed
+    /// explicitly to know that every packet reached the file.
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let mut f = LibPcap::open("test_close.pcap", "w").unwrap();
+    /// 
+    /// f.write(b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00").unwrap();
+    /// f.close().unwrap();
+    /// 
+    /// assert!(f.write(b"\x00").is_err());
+    /// ```
+    /// 
+    pub fn close(&mut self) -> PResult<()> {
+        let writing = !self.out_pcap.is_null();
+        let mut ret = self.flush();
+
+        unsafe {
+            if !self.out_pcap.is_null() {
+                pcap_dump_close(self.out_pcap);
+                self.out_pcap = null_mut();
+            }
+
+            if !self.dead_pcap.is_null() {
+                pcap_close(self.dead_pcap);
+                self.dead_pcap = null_mut();
+            }
+
+            if !self.in_pcap.is_null() {
+                pcap_close(self.in_pcap);
+                self.in_pcap = null_mut();
+            }
+        }
+
+        // Closing the stdio stream above ends a writer's pump, wait for the compressed trailer to be written.
+        // A reader's pump may be blocked reading stdin or a pipe, it is left to end at its next write,
+        // which fails now that the other end is closed.
+        if let Some(pump) = self.pump.take() {
+            if writing {
+                let value = pump.join().unwrap_or_else(|_| Err(LibPcapError::WriteError { msg: "output thread panicked".to_string() }));
+
+                if ret.is_ok() {
+                    ret = value;
+                }
+            }
+        }
+
+        ret
     }
 
     pub fn with_filter(&self, value: &'a str) -> Result<&Self, LibPcapError> {
@@ -172,8 +509,25 @@ impl<'a> LibPcap {
         Ok(&self)
     }
 
-    pub fn read(&self) -> LibPcapIterator<'a> {
-        LibPcapIterator::new(self.in_pcap)
+    /// Iterate over the packets of an input file
+    /// 
+    /// Iterating yields owned `Packet` values that can be collected, sorted or sent to other threads.
+    /// `LibPcapIterator::next_packet` borrows libpcap's buffer instead of copying it, which is why
+    /// a single iterator may exist at a time:
+    /// 
+    /// ```compile_fail
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    /// let mut a = f.read();
+    /// let mut b = f.read();
+    /// 
+    /// let pkt = a.next_packet();
+    /// b.next_packet();
+    /// println!("{pkt:?}");
+    /// ```
+    pub fn read(&mut self) -> LibPcapIterator<'_> {
+        LibPcapIterator::new(self)
     }
 
     /// Write to a pcap file with a custom timestamp
@@ -198,22 +552,96 @@ impl<'a> LibPcap {
     /// let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
     /// 
     /// match libpcap {
-    ///     Ok(f) => f.write_timestamp(input, timestamp as i64),
+    ///     Ok(f) => f.write_timestamp(input, timestamp as i64).unwrap(),
     ///     Err(e) => println!("[ERROR]: {e:?}"),
     /// }
     /// ```
     /// 
-    pub fn write_timestamp(&self, buf: &[u8], timestamp: i64) {
-        if !self.out_pcap.is_null() {
-            let pkt_header: std::mem::MaybeUninit<pcap_pkthdr> = std::mem::MaybeUninit::uninit();
-            let mut pkt_header = unsafe { pkt_header.assume_init() };
-    
-            pkt_header.caplen = buf.len() as u32;
-            pkt_header.len = pkt_header.caplen;
-            pkt_header.ts.tv_sec = timestamp;
-            
-            unsafe { pcap_dump(self.out_pcap as *mut u8, &pkt_header, buf.as_ptr()); };    
+    pub fn write_timestamp(&self, buf: &[u8], timestamp: i64) -> PResult<()> {
+        self.write_ts(buf, Duration::from_secs(timestamp as u64))
+    }
+
+    /// Write to a pcap file with a full precision timestamp
+    /// 
+    /// # Args:
+    /// 
+    /// - `buf`: Byte stream to be written
+    /// - `ts`: Duration since the UNIX epoch, truncated to the file timestamp precision
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+    /// 
+    /// let libpcap = LibPcap::open("test_ts.pcap", "w");
+    /// let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    /// 
+    /// match libpcap {
+    ///     Ok(f) => f.write_ts(input, ts).unwrap(),
+    ///     Err(e) => println!("[ERROR]: {e:?}"),
+    /// }
+    /// ```
+    /// 
+    pub fn write_ts(&self, buf: &[u8], ts: Duration) -> PResult<()> {
+        self.write_ts_with_len(buf, ts, buf.len() as u32)
+    }
+
+    /// Write to a pcap file keeping the original wire length
+    /// 
+    /// The captured length is `buf.len()`, `len` is the length of the packet on the wire
+    /// and is raised to `buf.len()` when smaller.
+    /// 
+    /// # Args:
+    /// 
+    /// - `buf`: Captured bytes to be written
+    /// - `ts`: Duration since the UNIX epoch
+    /// - `len`: Original length of the packet
+    /// 
+    pub fn write_ts_with_len(&self, buf: &[u8], ts: Duration, len: u32) -> PResult<()> {
+        if self.out_pcap.is_null() {
+            return Err(LibPcapError::OpenModeError);
+        }
+
+        let pkt_header: std::mem::MaybeUninit<pcap_pkthdr> = std::mem::MaybeUninit::uninit();
+        let mut pkt_header = unsafe { pkt_header.assume_init() };
+
+        pkt_header.caplen = buf.len() as u32;
+        pkt_header.len = len.max(pkt_header.caplen);
+        pkt_header.ts = duration_to_timeval(ts, self.precision);
+
+        unsafe { pcap_dump(self.out_pcap as *mut u8, &pkt_header, buf.as_ptr()); };
+
+        // pcap_dump can't fail, a failed fwrite sets the error flag of the stream
+        if unsafe { ferror(pcap_dump_file(self.out_pcap)) } != 0 {
+            return Err(LibPcapError::WriteError { msg: std::io::Error::last_os_error().to_string() });
         }
+
+        Ok(())
+    }
+
+    /// Write a packet read from another pcap file or capture, keeping its timestamp and wire length
+    /// 
+    /// Packets are buffered, call `flush` or `close` to know that they reached the output.
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let mut input = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    /// let output = LibPcap::open("test_copy.pcap", "w").unwrap();
+    /// 
+    /// let mut iter = input.read();
+    /// 
+    /// while let Some(pkt) = iter.next_packet() {
+    ///     output.write_packet(&pkt).unwrap();
+    /// }
+    /// ```
+    /// 
+    pub fn write_packet(&self, pkt: &LibPcapPacketInfo<'_>) -> PResult<()> {
+        self.write_ts_with_len(pkt.buf, pkt.ts, pkt.len)
     }
 
     /// Write to a pcap file
@@ -236,67 +664,144 @@ impl<'a> LibPcap {
     /// let libpcap = LibPcap::open("test.pcap", "w");
     /// 
     /// match libpcap {
-    ///     Ok(f) => f.write(input),
+    ///     Ok(f) => f.write(input).unwrap(),
     ///     Err(e) => println!("[ERROR]: {e:?}"),
     /// }
     /// ```
     /// 
-    pub fn write(&self, buf: &[u8]) {
-        self.write_timestamp(buf, now_timestamp() as i64)
+    pub fn write(&self, buf: &[u8]) -> PResult<()> {
+        self.write_ts(buf, now_duration())
     }
 }
 
 
 #[derive(Debug)]
 pub struct LibPcapIterator<'a> {
-    in_pcap: *mut pcap_t,
-    phantom: PhantomData<&'a [u8]>,
+    pcap: &'a mut LibPcap,
+    error: Option<LibPcapError>,
 }
 
 
 impl<'a> LibPcapIterator<'a> {
-    pub fn new(in_pcap: *mut pcap_t) -> Self {
+    pub fn new(pcap: &'a mut LibPcap) -> Self {
         Self {
-            in_pcap,
-            phantom: PhantomData,
+            pcap,
+            error: None,
         }
     }
-}
 
+    /// Read the next packet without copying it
+    /// 
+    /// The returned buffer points into libpcap's own buffer and is overwritten by the next read,
+    /// so it borrows the iterator until it is dropped. Use `into_owned` to keep it.
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    /// let mut iter = f.read();
+    /// let mut packets = vec![];
+    /// 
+    /// while let Some(pkt) = iter.next_packet() {
+    ///     if pkt.caplen > 1000 {
+    ///         packets.push(pkt.into_owned());
+    ///     }
+    /// }
+    /// ```
+    /// 
+    pub fn next_packet(&mut self) -> Option<LibPcapPacketInfo<'_>> {
+        let in_pcap = self.pcap.in_pcap;
 
-impl<'a> Iterator for LibPcapIterator<'a> {
-    type Item = LibPcapPacketInfo<'a>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.in_pcap.is_null() {
+        if in_pcap.is_null() || self.error.is_some() {
             return None;
         }
 
-        let mut pkt_header = std::mem::MaybeUninit::uninit();
+        let mut pkt_header: *mut pcap_pkthdr = null_mut();
+        let mut pkt_data: *const u8 = std::ptr::null();
 
-        let pkt = unsafe {
-            let pkt = pcap_next(self.in_pcap, pkt_header.as_mut_ptr());
-            std::slice::from_raw_parts(pkt, pkt_header.assume_init().caplen as usize)
-        };
+        let ret = unsafe { pcap_next_ex(in_pcap, &mut pkt_header, &mut pkt_data) };
 
-        let pkt_header = unsafe { pkt_header.assume_init() };
+        match ret {
+            1 => {
+                let pkt_header = unsafe { *pkt_header };
+                let pkt = unsafe { std::slice::from_raw_parts(pkt_data, pkt_header.caplen as usize) };
 
-        if pkt.is_empty() {
-            None
-        }
-        else {
-            Some(Self::Item {
-                timestamp: pkt_header.ts.tv_sec,
-                caplen: pkt_header.caplen,
-                buf: &pkt,
-            })
+                Some(LibPcapPacketInfo {
+                    timestamp: pkt_header.ts.tv_sec,
+                    ts: timeval_to_duration(&pkt_header.ts, self.pcap.precision),
+                    caplen: pkt_header.caplen,
+                    len: pkt_header.len,
+                    buf: pkt,
+                })
+            },
+            PCAP_ERROR_BREAK => {
+                // libpcap sees a clean end of file when a compressed input is cut short on a record boundary,
+                // the pump has finished by now and tells whether the whole stream was read
+                if let Some(pump) = self.pcap.pump.take() {
+                    self.error = match pump.join() {
+                        Ok(Ok(())) => None,
+                        Ok(Err(LibPcapError::IoError(e))) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                            Some(LibPcapError::TruncatedRecord { msg: e.to_string() })
+                        },
+                        Ok(Err(e)) => Some(LibPcapError::ReadError { msg: e.to_string() }),
+                        Err(_) => Some(LibPcapError::ReadError { msg: "input thread panicked".to_string() }),
+                    };
+                }
+
+                None
+            },
+            _ => {
+                let msg = make_cstr!(pcap_geterr(in_pcap));
+
+                // libpcap reports short reads of savefiles as "truncated dump file; ..."
+                self.error = Some(if msg.contains("truncated") {
+                    LibPcapError::TruncatedRecord { msg }
+                }
+                else {
+                    LibPcapError::ReadError { msg }
+                });
+
+                None
+            },
         }
     }
+
+    /// Error that stopped the iteration, `None` if the file ended cleanly
+    /// 
+    /// # Example:
+    /// 
+    /// ```rust
+    /// use libpcap_rs::LibPcap;
+    /// 
+    /// let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    /// let mut iter = f.read();
+    /// 
+    /// for _ in iter.by_ref() { }
+    /// 
+    /// assert!(iter.last_error().is_none());
+    /// ```
+    /// 
+    pub fn last_error(&self) -> Option<&LibPcapError> {
+        self.error.as_ref()
+    }
+}
+
+
+impl<'a> Iterator for LibPcapIterator<'a> {
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().map(LibPcapPacketInfo::into_owned)
+    }
 } 
 
 
 /// Obtain the first active network port
 /// 
+/// The first up and running non-loopback interface, else the first non-loopback one.
+/// 
 /// # Returns:
 /// 
 /// - `PResult<String>`
@@ -310,18 +815,15 @@ impl<'a> Iterator for LibPcapIterator<'a> {
 /// ```
 /// 
 pub fn get_first_iface() -> PResult<String> {
-    let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
-
-    let value = unsafe { pcap_lookupdev(errbuf.as_mut_ptr()) };
-
-    if value.is_null() {
-        Err(LibPcapError::LookUpDevError {
-            msg: make_cstr!(errbuf.as_ptr()),
+    let interfaces = get_interfaces()?;
+
+    interfaces.iter()
+        .find(|v| v.is_usable())
+        .or_else(|| interfaces.iter().find(|v| !v.is_loopback()))
+        .map(|v| v.name.clone())
+        .ok_or_else(|| LibPcapError::LookUpDevError {
+            msg: "no suitable device found".to_string(),
         })
-    }
-    else {
-        Ok(make_cstr!(value))
-    }
 }
 
 
@@ -340,35 +842,15 @@ pub fn get_first_iface() -> PResult<String> {
 /// ```
 /// 
 pub fn get_iface_list() -> PResult<Vec<String>> {
-    let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
-    let mut interfaces = std::mem::MaybeUninit::uninit();
-    let mut interface_list = vec![];
-
-    let ret = unsafe { pcap_findalldevs(interfaces.as_mut_ptr(), errbuf.as_mut_ptr()) };
-
-    if ret == -1 {
-        return Err(LibPcapError::FindAllDevsError {
-            msg: make_cstr!(errbuf.as_ptr()),
-        })
-    }
-
-    let interfaces = unsafe { interfaces.assume_init() };
-
-    let mut interface_temp = interfaces;
-
-    while !interface_temp.is_null() {
-        interface_list.push(make_cstr!((*interface_temp).name));
-        interface_temp = unsafe {(*interface_temp).next};
-    }
-
-    unsafe { pcap_freealldevs(interfaces) };
-
-    Ok(interface_list)
+    Ok(get_interfaces()?.into_iter().map(|v| v.name).collect())
 }
 
 
 /// Using network port send raw packet
 /// 
+/// Opens and closes the device for this one packet, use `Sniff::send` or `Sniff::inject`
+/// to send many packets.
+/// 
 /// # Args:
 /// 
 /// - `iface: T`: network port name
@@ -392,25 +874,9 @@ pub fn get_iface_list() -> PResult<Vec<String>> {
 /// ```
 /// 
 pub fn send_packet<T: Into<Vec<u8>>>(iface: T, buf: &[u8]) -> PResult<()> {
-    let mut errbuf = [0; PCAP_ERRBUF_SIZE as usize];
-    let iface = CString::new(iface).unwrap_or_default();
-
-    let handle = unsafe { pcap_open_live(iface.as_ptr(), 65535, 0, 0, errbuf.as_mut_ptr()) };
-
-    if handle.is_null() {
-        return Err(LibPcapError::InvalidInterface {
-            iface: make_cstr!(iface.as_ptr()),
-            msg: make_cstr!(errbuf.as_ptr()),
-        });
-    }
-
-    let ret = unsafe { pcap_sendpacket(handle, buf.as_ptr(), buf.len() as i32) };
-
-    if ret == -1 {
-        return Err(LibPcapError::SendRawPacketError);
-    }
+    let sniff = Sniff::open(iface.into())?;
 
-    unsafe { pcap_close(handle) };
+    sniff.send(buf)?;
 
     Ok(())
 }
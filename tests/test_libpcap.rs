@@ -1,6 +1,8 @@
+#![cfg(feature = "libpcap")]
 // use std::path::Path;
 #[allow(unused_imports)]
-use libpcap_rs::{LibPcap, get_first_iface, get_iface_list};
+use libpcap_rs::{LibPcap, DataLink, get_first_iface, get_iface_list};
+use libpcap_rs::wrapper::TstampPrecision;
 
 
 #[test]
@@ -24,12 +26,82 @@ fn test_write_pcap() {
     assert_eq!(f.is_ok(), true);
 
     match f {
-        Ok(f) => f.write(input),
+        Ok(f) => f.write(input).unwrap(),
         Err(e) => println!("[ERROR]: {e:?}"),
     }
 }
 
 
+#[test]
+fn test_write_pcap_nano_timestamp() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+    let ts = std::time::Duration::new(1700000000, 123456789);
+
+    {
+        let f = LibPcap::open_with_tstamp_precision("test_nano.pcap", "w", TstampPrecision::Nano).unwrap();
+        f.write_ts(input, ts).unwrap();
+    }
+
+    let mut f = LibPcap::open_with_tstamp_precision("test_nano.pcap", "r", TstampPrecision::Nano).unwrap();
+    let pkt = f.read().next().unwrap();
+    assert_eq!(pkt.ts, ts);
+    assert_eq!(pkt.timestamp, 1700000000);
+
+    let mut f = LibPcap::open("test_nano.pcap", "r").unwrap();
+    let pkt = f.read().next().unwrap();
+    assert_eq!(pkt.ts, std::time::Duration::new(1700000000, 123456000));
+}
+
+
+#[test]
+fn test_write_pcap_keep_wire_length() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+
+    {
+        let f = LibPcap::open("test_len.pcap", "w").unwrap();
+        f.write_ts_with_len(input, std::time::Duration::from_secs(1700000000), 1514).unwrap();
+    }
+
+    let mut f = LibPcap::open("test_len.pcap", "r").unwrap();
+    let pkt = f.read().next().unwrap();
+    assert_eq!(pkt.caplen, input.len() as u32);
+    assert_eq!(pkt.len, 1514);
+}
+
+
+#[test]
+fn test_write_pcap_linktype() {
+    let input = b"\x00\x00\x00\x01\x00\x06\x00\x0c\x29\xaf\x7f\xfe\x00\x00\x08\x00\x45\x00";
+
+    {
+        let f = LibPcap::open_with_linktype("test_sll.pcap", "w", 113, 262144).unwrap();
+        f.write(input).unwrap();
+    }
+
+    let f = LibPcap::open("test_sll.pcap", "r").unwrap();
+    assert_eq!(f.linktype(), 113);
+    assert_eq!(f.snaplen(), 262144);
+
+    let output = LibPcap::open_like("test_sll_copy.pcap", "w", &f).unwrap();
+    assert_eq!(output.linktype(), 113);
+    assert_eq!(output.snaplen(), 262144);
+}
+
+
+#[test]
+fn test_datalink() {
+    let f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    assert_eq!(f.datalink(), DataLink::En10mb);
+    assert_eq!(f.datalink().description().as_deref(), Some("Ethernet"));
+
+    assert_eq!("LINUX_SLL".parse::<DataLink>().ok(), Some(DataLink::LinuxSll));
+    assert_eq!("DLT_NULL".parse::<DataLink>().ok(), Some(DataLink::Null));
+    assert!("NOT_A_LINKTYPE".parse::<DataLink>().is_err());
+    assert_eq!(DataLink::from(DataLink::Ieee80211Radio.value()), DataLink::Ieee80211Radio);
+    assert_eq!(DataLink::from(4242), DataLink::Other(4242));
+}
+
+
 #[test]
 fn test_get_first_iface() {
     // assert_eq!(get_first_iface().is_ok(), true);
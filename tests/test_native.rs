@@ -0,0 +1,563 @@
+use std::time::Duration;
+use libpcap_rs::{DataLink, LibPcapError};
+use libpcap_rs::native::{LibPcap, IndexedReader, PcapIndex, PcapngReader, PcapngWriter, SectionHeader, InterfaceDescription, PacketOptions};
+use libpcap_rs::packet::{FileFormat, TstampPrecision};
+use libpcap_rs::compress::Compression;
+use libpcap_rs::path::is_capture_file;
+use libpcap_rs::rotate::RotatingWriter;
+use libpcap_rs::split::{SplitBy, Splitter};
+use libpcap_rs::merge::{MergeIterator, MergeMode};
+use libpcap_rs::edit::{Dedup, DedupWindow};
+use libpcap_rs::info::CaptureInfo;
+use libpcap_rs::stats::CaptureStats;
+
+
+#[test]
+fn test_native_read_pcap() {
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    assert_eq!(f.datalink(), DataLink::En10mb);
+    assert_eq!(f.snaplen(), 65535);
+
+    let packets: Vec<_> = f.read().collect();
+    assert!(!packets.is_empty());
+    assert_eq!(packets[0].ts, Duration::new(1084443427, 311224000));
+    assert_eq!(packets[0].caplen as usize, packets[0].buf.len());
+
+    for pkt in &packets {
+        assert!(pkt.len >= pkt.caplen);
+    }
+}
+
+
+#[test]
+fn test_native_write_pcap() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+    let ts = Duration::new(1700000000, 123456789);
+
+    {
+        let f = LibPcap::open_with_tstamp_precision("test_native_nano.pcap", "w", TstampPrecision::Nano).unwrap();
+        f.write_ts_with_len(input, ts, 1514).unwrap();
+    }
+
+    {
+        let f = LibPcap::open_with_tstamp_precision("test_native_nano.pcap", "a", TstampPrecision::Nano).unwrap();
+        f.write_ts(input, ts + Duration::from_nanos(1)).unwrap();
+    }
+
+    let mut f = LibPcap::open_with_tstamp_precision("test_native_nano.pcap", "r", TstampPrecision::Nano).unwrap();
+    let packets: Vec<_> = f.read().collect();
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0].ts, ts);
+    assert_eq!(packets[0].len, 1514);
+    assert_eq!(packets[0].buf, input);
+    assert_eq!(packets[1].ts, ts + Duration::from_nanos(1));
+
+    let mut f = LibPcap::open("test_native_nano.pcap", "r").unwrap();
+    assert_eq!(f.read().next().unwrap().ts, Duration::new(1700000000, 123456000));
+}
+
+
+#[test]
+fn test_native_close_pcap() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+
+    let mut f = LibPcap::open("test_native_close.pcap", "w").unwrap();
+    f.write(input).unwrap();
+    f.sync().unwrap();
+    assert_eq!(std::fs::metadata("test_native_close.pcap").unwrap().len(), 24 + 16 + input.len() as u64);
+    f.close().unwrap();
+    f.close().unwrap();
+    assert!(matches!(f.write(input), Err(LibPcapError::OpenModeError)));
+
+    let mut f = LibPcap::open("test_native_close.pcap", "r").unwrap();
+    assert!(matches!(f.write(input), Err(LibPcapError::OpenModeError)));
+    assert_eq!(f.read().count(), 1);
+
+    #[cfg(feature = "libpcap")]
+    {
+        let mut f = libpcap_rs::wrapper::LibPcap::open("test_native_close.pcap", "a").unwrap();
+        f.write(input).unwrap();
+        f.sync().unwrap();
+        f.close().unwrap();
+        assert!(matches!(f.write(input), Err(LibPcapError::OpenModeError)));
+        assert_eq!(LibPcap::open("test_native_close.pcap", "r").unwrap().read().count(), 2);
+    }
+}
+
+
+#[test]
+fn test_native_owned_packets() {
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let packets: Vec<_> = f.read().collect();
+
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let mut iter = f.read();
+    let mut count = 0;
+
+    while let Some(pkt) = iter.next_packet() {
+        assert_eq!(pkt, packets[count].as_info());
+        count += 1;
+    }
+
+    assert_eq!(count, packets.len());
+
+    #[cfg(feature = "libpcap")]
+    {
+        let mut f = libpcap_rs::wrapper::LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+        assert_eq!(f.read().collect::<Vec<_>>(), packets);
+    }
+
+    let size = std::thread::spawn(move || packets.iter().map(|v| v.buf.len()).sum::<usize>()).join().unwrap();
+    assert!(size > 0);
+}
+
+
+#[test]
+fn test_native_indexed_reader() {
+    std::fs::copy("tests/pcap/http_1.pcap", "test_native_index.pcap").unwrap();
+    let _ = std::fs::remove_file(PcapIndex::sidecar_path("test_native_index.pcap"));
+
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let packets: Vec<_> = f.read().collect();
+
+    let mut reader = IndexedReader::open_with_sidecar("test_native_index.pcap").unwrap();
+    assert_eq!(reader.len(), packets.len());
+    assert_eq!(reader.get(10).unwrap().as_ref(), Some(&packets[10]));
+    assert_eq!(reader.range(3..5).map(|v| v.unwrap()).collect::<Vec<_>>(), packets[3..5]);
+
+    let n = reader.seek_to_time(packets[20].ts);
+    assert!(n <= 20);
+    assert_eq!(reader.next_packet().unwrap().unwrap().ts, packets[n].ts);
+
+    let index = PcapIndex::load(PcapIndex::sidecar_path("test_native_index.pcap")).unwrap();
+    assert_eq!(&index, reader.index());
+
+    let start = packets[5].ts;
+    let end = packets[15].ts;
+    let expected: Vec<_> = packets.iter().filter(|v| v.ts >= start && v.ts < end).cloned().collect();
+    let mut reader = IndexedReader::open_with_sidecar("test_native_index.pcap").unwrap();
+    assert_eq!(reader.time_range(start, end).map(|v| v.unwrap()).collect::<Vec<_>>(), expected);
+}
+
+
+#[test]
+fn test_native_rotate_pcap() {
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let packets: Vec<_> = f.read().collect();
+    let closed = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let closed_clone = closed.clone();
+
+    let mut writer = RotatingWriter::new("test_native_rotate.pcap", f.linktype(), f.snaplen())
+        .with_max_packets(10)
+        .with_max_files(2)
+        .on_close(move |path| closed_clone.lock().unwrap().push(path.to_path_buf()));
+
+    for pkt in &packets {
+        writer.write_packet(&pkt.as_info()).unwrap();
+    }
+
+    writer.close().unwrap();
+
+    let count = packets.len().div_ceil(10);
+    let files: Vec<_> = writer.files().iter().cloned().collect();
+    assert_eq!(closed.lock().unwrap().len(), count);
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[1].to_str(), Some(format!("test_native_rotate.{}.pcap", count - 1).as_str()));
+    assert!(!std::path::Path::new("test_native_rotate.0.pcap").exists());
+
+    let mut f = LibPcap::open(files[0].to_str().unwrap(), "r").unwrap();
+    assert_eq!(f.read().collect::<Vec<_>>(), packets[(count - 2) * 10..(count - 1) * 10]);
+
+    // A pattern coarser than the interval doesn't truncate the previous file
+    let mut writer = RotatingWriter::new("test_native_rotate-%Y.pcap", f.linktype(), f.snaplen())
+        .with_interval(Duration::from_secs(60));
+
+    for (i, pkt) in packets[..3].iter().enumerate() {
+        writer.write_ts_with_len(&pkt.buf, Duration::from_secs(i as u64 * 60), pkt.len).unwrap();
+    }
+
+    writer.close().unwrap();
+
+    let files: Vec<_> = writer.files().iter().cloned().collect();
+    assert_eq!(files.len(), 3);
+
+    for (path, pkt) in files.iter().zip(&packets) {
+        let mut f = LibPcap::open(path.to_str().unwrap(), "r").unwrap();
+        assert_eq!(f.read().map(|v| v.buf).collect::<Vec<_>>(), std::slice::from_ref(&pkt.buf));
+    }
+}
+
+
+#[test]
+fn test_native_split_pcap() {
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let packets: Vec<_> = f.read().collect();
+
+    for (by, count) in [(SplitBy::Packets(20), packets.len().div_ceil(20)), (SplitBy::Flow, 3)] {
+        let mut splitter = Splitter::new(by, "test_native_split.pcap", f.linktype(), f.snaplen()).with_max_open(1);
+
+        for pkt in &packets {
+            splitter.write_packet(&pkt.as_info()).unwrap();
+        }
+
+        splitter.close().unwrap();
+        assert_eq!(splitter.files().len(), count);
+
+        let mut output = vec![];
+
+        for path in splitter.files() {
+            output.extend(LibPcap::open(path.to_str().unwrap(), "r").unwrap().read());
+            std::fs::remove_file(path).unwrap();
+        }
+
+        output.sort_by_key(|v| v.ts);
+        assert_eq!(output, packets);
+    }
+
+    // Bytes 12-13 of a raw IP packet look like an 802.1Q ethertype, it is no VLAN
+    let raw = b"\x45\x00\x00\x20\x00\x00\x00\x00\x40\x11\x00\x00\x81\x00\x00\x05\x0a\x00\x00\x02";
+    let mut splitter = Splitter::new(SplitBy::Vlan, "test_native_split_vlan.pcap", DataLink::Raw.linktype() as i32, 65535);
+    splitter.write_packet(&libpcap_rs::Packet::new(raw.to_vec(), Duration::new(1, 0), raw.len() as u32).as_info()).unwrap();
+    splitter.close().unwrap();
+
+    assert_eq!(splitter.files().len(), 1);
+    assert!(splitter.files()[0].to_string_lossy().contains("other"));
+}
+
+
+#[test]
+fn test_native_dedup_merge() {
+    let packets: Vec<_> = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap().read().collect();
+    let mut inputs = [
+        LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap(),
+        LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap(),
+    ];
+
+    let merged = MergeIterator::new(inputs.iter_mut().map(|v| v.read()), MergeMode::Time).map(|(_, v)| v);
+    let mut iter = Dedup::with_window(DedupWindow::Time(Duration::ZERO)).dedup(merged);
+    let output: Vec<_> = iter.by_ref().collect();
+
+    assert_eq!(output, packets);
+    assert_eq!(iter.removed(), packets.len() as u64);
+}
+
+
+#[test]
+fn test_native_compressed_pcap() {
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let packets: Vec<_> = f.read().collect();
+
+    for compression in [Compression::Gzip, Compression::Zstd, Compression::Xz] {
+        let path = format!("test_native_compressed.pcap.{}", compression.extension().unwrap());
+        assert_eq!(is_capture_file(&path), compression.is_supported());
+
+        if !compression.is_supported() {
+            assert!(matches!(LibPcap::open(&path, "w"), Err(LibPcapError::UnsupportedCompression { .. })));
+
+            let magic: &[u8] = match compression {
+                Compression::Gzip => b"\x1f\x8b\x08\x00",
+                Compression::Zstd => b"\x28\xb5\x2f\xfd",
+                _ => b"\xfd\x37\x7a\x58\x5a\x00",
+            };
+
+            std::fs::write(&path, magic).unwrap();
+            assert!(matches!(LibPcap::open(&path, "r"), Err(LibPcapError::UnsupportedCompression { .. })));
+
+            #[cfg(feature = "libpcap")]
+            assert!(matches!(libpcap_rs::wrapper::LibPcap::open(&path, "r"), Err(LibPcapError::UnsupportedCompression { .. })));
+
+            continue;
+        }
+
+        for (mode, chunk) in [("w", &packets[..10]), ("a", &packets[10..])] {
+            let f = LibPcap::open(&path, mode).unwrap();
+
+            for pkt in chunk {
+                f.write_packet(&pkt.as_info()).unwrap();
+            }
+        }
+
+        assert_eq!(Compression::detect_file(&path), compression);
+
+        let mut f = LibPcap::open(&path, "r").unwrap();
+        assert_eq!(f.read().collect::<Vec<_>>(), packets);
+
+        #[cfg(feature = "libpcap")]
+        {
+            let mut f = libpcap_rs::wrapper::LibPcap::open(&path, "r").unwrap();
+            assert_eq!(f.read().collect::<Vec<_>>(), packets);
+
+            let output = format!("test_wrapper_compressed.pcap.{}", compression.extension().unwrap());
+
+            for (mode, chunk) in [("w", &packets[..10]), ("a", &packets[10..])] {
+                let f = libpcap_rs::wrapper::LibPcap::open(&output, mode).unwrap();
+
+                for pkt in chunk {
+                    f.write_packet(&pkt.as_info()).unwrap();
+                }
+            }
+
+            let mut f = LibPcap::open(&output, "r").unwrap();
+            assert_eq!(f.read().collect::<Vec<_>>(), packets);
+        }
+    }
+}
+
+
+#[test]
+fn test_native_stream_pcap() {
+    let buf = std::fs::read("tests/pcap/http_1.pcap").unwrap();
+
+    let mut f = LibPcap::from_reader(std::io::Cursor::new(buf.clone()), TstampPrecision::Micro).unwrap();
+    assert_eq!(f.datalink(), DataLink::En10mb);
+    let packets: Vec<_> = f.read().collect();
+
+    {
+        let file = std::fs::File::create("test_native_stream.pcap").unwrap();
+        let f = LibPcap::from_writer(file, f.linktype(), f.snaplen(), TstampPrecision::Micro).unwrap();
+
+        for pkt in &packets {
+            f.write_packet(&pkt.as_info()).unwrap();
+        }
+    }
+
+    assert_eq!(std::fs::read("test_native_stream.pcap").unwrap(), buf);
+
+    #[cfg(feature = "libpcap")]
+    {
+        let mut f = libpcap_rs::wrapper::LibPcap::from_reader(std::io::Cursor::new(buf.clone()), TstampPrecision::Micro).unwrap();
+        assert_eq!(f.read().collect::<Vec<_>>(), packets);
+
+        {
+            let file = std::fs::File::create("test_wrapper_stream.pcap").unwrap();
+            let f = libpcap_rs::wrapper::LibPcap::from_writer(file, f.linktype(), f.snaplen(), TstampPrecision::Micro).unwrap();
+
+            for pkt in &packets {
+                f.write_packet(&pkt.as_info()).unwrap();
+            }
+        }
+
+        assert_eq!(std::fs::read("test_wrapper_stream.pcap").unwrap(), buf);
+    }
+}
+
+
+#[test]
+fn test_native_truncated_pcap() {
+    let buf = std::fs::read("tests/pcap/http_1.pcap").unwrap();
+    std::fs::write("test_native_truncated.pcap", &buf[..buf.len() - 10]).unwrap();
+
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let mut iter = f.read();
+    let count = iter.by_ref().count();
+    assert!(iter.last_error().is_none());
+
+    let mut f = LibPcap::open("test_native_truncated.pcap", "r").unwrap();
+    let mut iter = f.read();
+    assert_eq!(iter.by_ref().count(), count - 1);
+    assert!(matches!(iter.last_error(), Some(LibPcapError::TruncatedRecord { .. })));
+
+    #[cfg(feature = "libpcap")]
+    {
+        let mut f = libpcap_rs::wrapper::LibPcap::open("test_native_truncated.pcap", "r").unwrap();
+        let mut iter = f.read();
+        assert_eq!(iter.by_ref().count(), count - 1);
+        assert!(matches!(iter.last_error(), Some(LibPcapError::TruncatedRecord { .. })));
+
+        // A stream cut short on a record boundary, as a damaged compressed file may be
+        struct CutShort;
+
+        impl std::io::Read for CutShort {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::UnexpectedEof.into())
+            }
+        }
+
+        let reader = std::io::Read::chain(std::io::Cursor::new(buf), CutShort);
+        let mut f = libpcap_rs::wrapper::LibPcap::from_reader(reader, TstampPrecision::Micro).unwrap();
+        let mut iter = f.read();
+        assert_eq!(iter.by_ref().count(), count);
+        assert!(matches!(iter.last_error(), Some(LibPcapError::TruncatedRecord { .. })));
+    }
+}
+
+
+#[test]
+fn test_native_capture_info() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+
+    {
+        let mut writer = PcapngWriter::open("test_native_info.pcapng", "w", SectionHeader::default()).unwrap();
+
+        writer.add_interface(InterfaceDescription::new(DataLink::En10mb, 65535)).unwrap();
+        writer.add_interface(InterfaceDescription::new(DataLink::LinuxSll, 262144)).unwrap();
+        writer.write_packet(0, input, Duration::new(1700000002, 0), 60).unwrap();
+        writer.write_packet(1, &input[..8], Duration::new(1700000000, 0), 8).unwrap();
+        writer.write_packet(0, input, Duration::new(1700000004, 0), 16).unwrap();
+    }
+
+    let info = CaptureInfo::open("test_native_info.pcapng").unwrap();
+    std::fs::remove_file("test_native_info.pcapng").unwrap();
+
+    assert_eq!(info.format, FileFormat::Pcapng);
+    assert_eq!(info.datalink, DataLink::En10mb);
+    assert_eq!(info.interfaces, 2);
+    assert_eq!(info.packets, 3);
+    assert_eq!(info.data_bytes, 40);
+    assert_eq!(info.wire_bytes, 84);
+    assert_eq!(info.truncated, 1);
+    assert_eq!(info.duration(), Some(Duration::from_secs(4)));
+    assert_eq!(info.packet_rate(), Some(0.75));
+    assert_eq!((info.min_size, info.max_size), (Some(8), Some(16)));
+    assert!(!info.time_ordered);
+    assert!(info.error.is_none());
+    assert!(info.to_json().contains("\"first_ts\": 1700000000.000000,"));
+
+    // The interfaces of every section are counted
+    for (mode, count) in [("w", 2), ("a", 1)] {
+        let mut writer = PcapngWriter::open("test_native_info_sections.pcapng", mode, SectionHeader::default()).unwrap();
+
+        for _ in 0..count {
+            writer.add_interface(InterfaceDescription::new(DataLink::En10mb, 65535)).unwrap();
+        }
+
+        writer.write_packet(0, input, Duration::new(1700000000, 0), 16).unwrap();
+    }
+
+    let info = CaptureInfo::open("test_native_info_sections.pcapng").unwrap();
+    std::fs::remove_file("test_native_info_sections.pcapng").unwrap();
+
+    assert_eq!((info.interfaces, info.packets), (3, 2));
+}
+
+
+#[test]
+fn test_native_capture_stats() {
+    let mut f = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let mut stats = CaptureStats::new(f.datalink());
+
+    for pkt in f.read() {
+        stats.add_packet(&pkt.as_info());
+    }
+
+    assert_eq!(stats.hierarchy.packets, 43);
+    assert_eq!(stats.hierarchy.get(&["eth", "ip", "tcp"]).unwrap().packets, 41);
+    assert_eq!(stats.hierarchy.get(&["eth", "ip", "udp", "dns"]).unwrap().bytes, 277);
+    assert_eq!(stats.eth.len(), 1);
+    assert_eq!(stats.ip.len(), 3);
+
+    let client = "145.254.160.237:3372".parse().unwrap();
+    let server = "65.208.228.223:80".parse().unwrap();
+    let conv = stats.tcp.get(&server, &client).unwrap();
+
+    assert_eq!(conv.a, client);
+    assert_eq!((conv.packets_ab, conv.packets_ba), (16, 18));
+    assert_eq!(conv.bytes(), 20695);
+    assert_eq!(conv.duration(), Duration::new(30, 393704000));
+
+    // Ethernet, IPv6 with a destination options header, UDP to port 53 with a 4 bytes payload
+    let mut frame = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x86\xdd".to_vec();
+    frame.extend_from_slice(b"\x60\x00\x00\x00\x00\x14\x3c\x40");
+    frame.extend_from_slice(&"2001:db8::1".parse::<std::net::Ipv6Addr>().unwrap().octets());
+    frame.extend_from_slice(&"2001:db8::2".parse::<std::net::Ipv6Addr>().unwrap().octets());
+    frame.extend_from_slice(b"\x11\x00\x00\x00\x00\x00\x00\x00");
+    frame.extend_from_slice(b"\xc0\x00\x00\x35\x00\x0c\x00\x00abcd");
+
+    let mut stats = CaptureStats::new(DataLink::En10mb);
+    stats.add_packet(&libpcap_rs::Packet::new(frame.clone(), Duration::new(1, 0), frame.len() as u32).as_info());
+
+    assert_eq!(stats.hierarchy.get(&["eth", "ipv6", "udp", "dns"]).unwrap().packets, 1);
+    assert!(stats.ip.get(&"2001:db8::1".parse().unwrap(), &"2001:db8::2".parse().unwrap()).is_some());
+    assert!(stats.udp.get(&"[2001:db8::1]:49152".parse().unwrap(), &"[2001:db8::2]:53".parse().unwrap()).is_some());
+}
+
+
+#[test]
+fn test_native_pcapng_file() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+
+    {
+        let mut writer = PcapngWriter::open("test_native.pcapng", "w", SectionHeader::default()).unwrap();
+        let mut eth0 = InterfaceDescription::new(DataLink::En10mb, 65535);
+        eth0.name = Some("eth0".to_string());
+        let mut any = InterfaceDescription::new(DataLink::LinuxSll, 262144).with_tstamp_precision(TstampPrecision::Nano);
+        any.name = Some("any".to_string());
+
+        writer.add_interface(eth0).unwrap();
+        writer.add_interface(any).unwrap();
+        writer.write_packet(0, input, Duration::new(1700000000, 1000), 60).unwrap();
+        writer.write_packet_with_options(1, input, Duration::new(1700000000, 2001), 60, &PacketOptions {
+            comment: Some("from any".to_string()),
+            ..Default::default()
+        }).unwrap();
+    }
+
+    let mut reader = PcapngReader::open("test_native.pcapng").unwrap();
+    let mut ifaces = vec![];
+
+    while let Some(pkt) = reader.next_packet().unwrap() {
+        let iface = reader.interface(pkt.interface_id).unwrap();
+        ifaces.push((iface.name.clone().unwrap(), iface.datalink, pkt.packet.ts, pkt.options.comment));
+    }
+
+    assert_eq!(ifaces, vec![
+        ("eth0".to_string(), DataLink::En10mb, Duration::new(1700000000, 1000), None),
+        ("any".to_string(), DataLink::LinuxSll, Duration::new(1700000000, 2001), Some("from any".to_string())),
+    ]);
+
+    let mut f = LibPcap::open_with_tstamp_precision("test_native.pcapng", "r", TstampPrecision::Nano).unwrap();
+    assert_eq!(f.datalink(), DataLink::En10mb);
+    assert_eq!(f.read().map(|v| v.ts).collect::<Vec<_>>(), vec![Duration::new(1700000000, 1000), Duration::new(1700000000, 2001)]);
+
+    #[cfg(feature = "libpcap")]
+    {
+        let mut f = libpcap_rs::wrapper::LibPcap::open("test_native.pcapng", "r").unwrap();
+        assert_eq!(f.read().map(|v| v.len).collect::<Vec<_>>(), vec![60, 60]);
+    }
+
+    // A new section goes after a pcapng file, never after a classic pcap one
+    assert!(PcapngWriter::open("test_native.pcapng", "a", SectionHeader::default()).is_ok());
+    std::fs::copy("tests/pcap/http_1.pcap", "test_native_append.pcap").unwrap();
+    assert!(matches!(PcapngWriter::open("test_native_append.pcap", "a", SectionHeader::default()), Err(LibPcapError::InvalidFile { .. })));
+    assert_eq!(std::fs::read("test_native_append.pcap").unwrap(), std::fs::read("tests/pcap/http_1.pcap").unwrap());
+}
+
+
+#[cfg(feature = "libpcap")]
+#[test]
+fn test_native_compat_libpcap() {
+    let input = b"\x00\x0c\x29\xaf\x7f\xfe\x10\x9a\xdd\x4e\x06\x0d\x08\x00\x45\x00";
+    let ts = Duration::new(1700000000, 123456789);
+
+    {
+        let f = libpcap_rs::wrapper::LibPcap::open_with_linktype("test_compat_c.pcap", "w", 113, 262144).unwrap();
+        f.write_ts_with_len(input, ts, 60).unwrap();
+    }
+
+    let mut f = LibPcap::open("test_compat_c.pcap", "r").unwrap();
+    assert_eq!(f.datalink(), DataLink::LinuxSll);
+    assert_eq!(f.snaplen(), 262144);
+    let pkt = f.read().next().unwrap();
+    assert_eq!(pkt.ts, Duration::new(1700000000, 123456000));
+    assert_eq!(pkt.len, 60);
+    assert_eq!(pkt.buf, input);
+
+    {
+        let f = LibPcap::open_with_tstamp_precision("test_compat_native.pcap", "w", TstampPrecision::Nano).unwrap();
+        f.write_ts(input, ts).unwrap();
+    }
+
+    let mut f = libpcap_rs::wrapper::LibPcap::open_with_tstamp_precision("test_compat_native.pcap", "r", TstampPrecision::Nano).unwrap();
+    let pkt = f.read().next().unwrap();
+    assert_eq!(pkt.ts, ts);
+    assert_eq!(pkt.buf, input);
+
+    let mut reference = libpcap_rs::wrapper::LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+    let mut native = LibPcap::open("tests/pcap/http_1.pcap", "r").unwrap();
+
+    for (a, b) in reference.read().zip(native.read()) {
+        assert_eq!(a.ts, b.ts);
+        assert_eq!(a.len, b.len);
+        assert_eq!(a.buf, &b.buf[..]);
+    }
+}